@@ -24,11 +24,52 @@
 //!    (0,0)                   (N,0)
 //! ```
 
-use std::fmt;
-use std::ops::Range;
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[macro_use]
+extern crate alloc;
 #[macro_use]
 extern crate bitflags;
 
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+/// Clockwise rotation in 90 degree steps.
+#[derive(Debug, Copy, Clone)]
+pub enum Rotation {
+    Cw0,
+    Cw90,
+    Cw180,
+    Cw270,
+}
+
+impl Rotation {
+    pub fn rotate_cw(&self, n: i8) -> Rotation {
+        match ((*self as i16) + (n as i16) + 4) % 4 {
+            0 => Rotation::Cw0,
+            1 => Rotation::Cw90,
+            2 => Rotation::Cw180,
+            3 => Rotation::Cw270,
+            _ => panic!("never matched"),
+        }
+    }
+    pub fn cw(&self) -> Rotation {
+        self.rotate_cw(1)
+    }
+    pub fn ccw(&self) -> Rotation {
+        self.rotate_cw(-1)
+    }
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self::Cw0
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Grid<C> {
     num_rows: usize,
@@ -49,6 +90,33 @@ where
             cells: cells,
         }
     }
+
+    /// Create a grid from rows in visual order, i.e. the first row is the top.
+    /// Panics if rows have different lengths.
+    pub fn from_rows(rows: Vec<Vec<C>>) -> Grid<C> {
+        let num_rows = rows.len();
+        let num_cols = rows.first().map_or(0, |r| r.len());
+        let mut cells = Vec::with_capacity(num_cols * num_rows);
+        for row in rows.into_iter().rev() {
+            assert_eq!(num_cols, row.len(), "inconsistent row width");
+            cells.extend(row);
+        }
+        Grid {
+            num_cols,
+            num_rows,
+            cells,
+        }
+    }
+
+    /// Create a grid with `fill` at each of `cells` and defaults elsewhere.
+    /// Panics if any coordinate is out of the grid.
+    pub fn from_occupied(cols: usize, rows: usize, cells: &[(usize, usize)], fill: C) -> Grid<C> {
+        let mut grid = Grid::new(cols, rows, vec![]);
+        for &(x, y) in cells {
+            grid.set_cell(x, y, fill.clone());
+        }
+        grid
+    }
 }
 
 impl<C> Grid<C>
@@ -82,12 +150,58 @@ where
         self.cells[self.cell_index(x, y)].clone()
     }
 
+    /// Same as `set_cell` but return an error instead of panicking if out of bounds.
+    pub fn try_set_cell(&mut self, x: usize, y: usize, cell: C) -> Result<(), OutOfBounds> {
+        if !self.is_valid_cell_index(x, y) {
+            return Err(OutOfBounds { x, y });
+        }
+        self.set_cell(x, y, cell);
+        Ok(())
+    }
+
+    /// Same as `cell` but return None instead of panicking if out of bounds.
+    pub fn try_cell(&self, x: usize, y: usize) -> Option<C> {
+        if !self.is_valid_cell_index(x, y) {
+            return None;
+        }
+        Some(self.cell(x, y))
+    }
+
+    fn row_slice(&self, y: usize) -> &[C] {
+        assert!(y < self.num_rows);
+        &self.cells[y * self.num_cols..(y + 1) * self.num_cols]
+    }
+
+    /// Iterate over rows from the bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &[C]> {
+        (0..self.num_rows).map(move |y| self.row_slice(y))
+    }
+
+    /// Iterate over rows from the top.
+    pub fn rows_top_down(&self) -> impl Iterator<Item = &[C]> {
+        (0..self.num_rows).rev().map(move |y| self.row_slice(y))
+    }
+
+    /// Iterate over columns from the left, each from the bottom.
+    /// Columns are copied since they are not contiguous.
+    pub fn cols(&self) -> impl Iterator<Item = Vec<C>> + '_ {
+        (0..self.num_cols).map(move |x| (0..self.num_rows).map(|y| self.cell(x, y)).collect())
+    }
+
     pub fn fill_row(&mut self, y: usize, cell: C) {
         for x in 0..self.num_cols {
             self.set_cell(x, y, cell.clone());
         }
     }
 
+    /// Write `cells` to row `y` from left to right.
+    pub fn set_row(&mut self, y: usize, cells: &[C]) {
+        assert_eq!(self.num_cols, cells.len());
+        for (x, cell) in cells.iter().enumerate() {
+            self.set_cell(x, y, cell.clone());
+        }
+    }
+
     pub fn fill_rows(&mut self, y_range: Range<usize>, cell: C) {
         for y in y_range {
             self.fill_row(y, cell.clone());
@@ -108,6 +222,21 @@ where
         self
     }
 
+    /// Rotate by 180 degrees in place, i.e. swap (x, y) for (num_cols - 1 - x, num_rows - 1 - y).
+    pub fn flip180(&mut self) -> &mut Self {
+        self.cells.reverse();
+        self
+    }
+
+    pub fn rotate(&self, r: Rotation) -> Grid<C> {
+        match r {
+            Rotation::Cw0 => self.clone(),
+            Rotation::Cw90 => self.rotate1(),
+            Rotation::Cw180 => self.rotate2(),
+            Rotation::Cw270 => self.rotate3(),
+        }
+    }
+
     pub fn rotate1(&self) -> Grid<C> {
         let mut g = Grid::new(self.num_rows, self.num_cols, vec![]);
         for y in 0..self.num_rows {
@@ -118,16 +247,8 @@ where
         g
     }
     pub fn rotate2(&self) -> Grid<C> {
-        let mut g = Grid::new(self.num_cols, self.num_rows, vec![]);
-        for y in 0..self.num_rows {
-            for x in 0..self.num_cols {
-                g.set_cell(
-                    self.num_cols - 1 - x,
-                    self.num_rows - 1 - y,
-                    self.cell(x, y),
-                )
-            }
-        }
+        let mut g = self.clone();
+        g.flip180();
         g
     }
     pub fn rotate3(&self) -> Grid<C> {
@@ -149,6 +270,92 @@ where
         }
     }
 
+    /// Move rows in `range` by `by` rows, upward if positive, overwriting destination rows.
+    /// Vacated rows are filled with `placeholder` if any and rows moved out of bounds are dropped.
+    pub fn shift_rows(&mut self, range: Range<usize>, by: i32, placeholder: Option<C>) {
+        let range = range.start.min(self.num_rows)..range.end.min(self.num_rows);
+        let rows: Vec<Vec<C>> = range.clone().map(|y| self.row_slice(y).to_vec()).collect();
+        if let Some(cell) = placeholder {
+            for y in range.clone() {
+                self.fill_row(y, cell.clone());
+            }
+        }
+        for (y, row) in range.zip(rows) {
+            let dst_y = y as i32 + by;
+            if 0 <= dst_y && dst_y < self.num_rows as i32 {
+                self.set_row(dst_y as usize, &row);
+            }
+        }
+    }
+
+    /// Move all cells by (dx, dy).
+    /// Vacated cells are filled with default and cells moved out of bounds are dropped.
+    pub fn shift(&mut self, dx: i32, dy: i32) {
+        let mut cells = Vec::with_capacity(self.cells.len());
+        for y in 0..self.num_rows {
+            for x in 0..self.num_cols {
+                let src_x = x as i32 - dx;
+                let src_y = y as i32 - dy;
+                if src_x < 0
+                    || self.num_cols as i32 <= src_x
+                    || src_y < 0
+                    || self.num_rows as i32 <= src_y
+                {
+                    cells.push(C::default());
+                } else {
+                    cells.push(self.cell(src_x as usize, src_y as usize));
+                }
+            }
+        }
+        self.cells = cells;
+    }
+
+    /// Return a new grid copied from the region, clipped to the bounds.
+    pub fn crop(&self, range_x: Range<usize>, range_y: Range<usize>) -> Grid<C> {
+        let range_x = range_x.start.min(self.num_cols)..range_x.end.min(self.num_cols);
+        let range_y = range_y.start.min(self.num_rows)..range_y.end.min(self.num_rows);
+        let mut grid = Grid::new(range_x.len(), range_y.len(), vec![]);
+        grid.paste_region((0, 0), self, range_x, range_y);
+        grid
+    }
+
+    /// Copy cells in `src_range_x` x `src_range_y` of `src` to `self` with the bottom-left at `dst`.
+    /// Unlike `overlay`, empty cells are also copied. Cells out of bounds are ignored.
+    pub fn paste_region(
+        &mut self,
+        dst: (usize, usize),
+        src: &Grid<C>,
+        src_range_x: Range<usize>,
+        src_range_y: Range<usize>,
+    ) {
+        let src_range_x = src_range_x.start..src_range_x.end.min(src.num_cols);
+        let src_range_y = src_range_y.start..src_range_y.end.min(src.num_rows);
+        for sy in src_range_y.clone() {
+            let y = dst.1 + (sy - src_range_y.start);
+            if y >= self.num_rows {
+                break;
+            }
+            for sx in src_range_x.clone() {
+                let x = dst.0 + (sx - src_range_x.start);
+                if x >= self.num_cols {
+                    break;
+                }
+                self.set_cell(x, y, src.cell(sx, sy));
+            }
+        }
+    }
+
+    /// Compare with `other` cell by cell using `eq` instead of `PartialEq`.
+    pub fn equals_by(&self, other: &Self, eq: impl Fn(&C, &C) -> bool) -> bool {
+        self.num_cols == other.num_cols
+            && self.num_rows == other.num_rows
+            && self
+                .cells
+                .iter()
+                .zip(other.cells.iter())
+                .all(|(a, b)| eq(a, b))
+    }
+
     pub fn map(&mut self, cb: fn(C) -> C) {
         for y in 0..self.num_rows {
             for x in 0..self.num_cols {
@@ -156,6 +363,24 @@ where
             }
         }
     }
+
+    /// Return a new grid of the same size with `f` applied to each cell.
+    pub fn map_cells<D: Default + Clone, F: Fn(&C) -> D>(&self, f: F) -> Grid<D> {
+        Grid {
+            num_cols: self.num_cols,
+            num_rows: self.num_rows,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+
+    /// Iterate over all cells mutably with their coordinates, row by row from the bottom.
+    pub fn iter_mut_cells(&mut self) -> impl Iterator<Item = (usize, usize, &mut C)> {
+        let num_cols = self.num_cols;
+        self.cells
+            .iter_mut()
+            .enumerate()
+            .map(move |(i, c)| (i % num_cols, i / num_cols, c))
+    }
 }
 
 impl<C> PartialEq for Grid<C>
@@ -169,6 +394,40 @@ where
     }
 }
 
+impl<C> Grid<C>
+where
+    C: PartialEq,
+{
+    /// Return the number of differing cells. Both grids must have the same size.
+    pub fn hamming_distance(&self, other: &Grid<C>) -> usize {
+        assert_eq!(self.num_cols, other.num_cols);
+        assert_eq!(self.num_rows, other.num_rows);
+        self.cells
+            .iter()
+            .zip(other.cells.iter())
+            .filter(|(a, b)| a != b)
+            .count()
+    }
+}
+
+/// Error for accessing a cell out of the grid.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OutOfBounds {
+    pub x: usize,
+    pub y: usize,
+}
+
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cell ({}, {}) is out of bounds", self.x, self.y)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OutOfBounds {}
+#[cfg(not(feature = "std"))]
+impl core::error::Error for OutOfBounds {}
+
 pub trait IsEmpty {
     fn is_empty(&self) -> bool;
 }
@@ -181,12 +440,79 @@ bitflags! {
     }
 }
 
+impl OverlayResult {
+    pub fn is_overflow(&self) -> bool {
+        self.contains(OverlayResult::OVERFLOW)
+    }
+    pub fn is_overlap(&self) -> bool {
+        self.contains(OverlayResult::OVERLAP)
+    }
+    /// Whether the sub grid can not be put, i.e. either overflow or overlap.
+    pub fn blocks_placement(&self) -> bool {
+        !self.is_empty()
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct OverlayDetail {
+    pub result: OverlayResult,
+    /// Coordinates of the first overlapped cell.
+    pub overlap: Option<(i32, i32)>,
+    /// Coordinates of the first overflowed cell.
+    pub overflow: Option<(i32, i32)>,
+}
+
 impl<C> Grid<C>
 where
     C: Default + Clone + IsEmpty,
 {
+    /// Iterate over non-empty cells with their coordinates, row by row from the bottom.
+    pub fn iter_filled(&self) -> impl Iterator<Item = (usize, usize, &C)> {
+        let num_cols = self.num_cols;
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.is_empty())
+            .map(move |(i, c)| (i % num_cols, i / num_cols, c))
+    }
+
+    /// Return occupancy of row `y`, true for non-empty cells.
+    pub fn row_mask(&self, y: usize) -> Vec<bool> {
+        self.row_slice(y).iter().map(|c| !c.is_empty()).collect()
+    }
+
     pub fn is_row_filled(&self, y: usize) -> bool {
-        for x in 0..self.num_cols {
+        self.row_slice(y).iter().all(|c| !c.is_empty())
+    }
+
+    /// Return the number of non-empty cells in `range_x` x `range_y`.
+    pub fn count_filled_in(&self, range_x: Range<usize>, range_y: Range<usize>) -> usize {
+        assert!(range_x.end <= self.num_cols);
+        assert!(range_y.end <= self.num_rows);
+        let mut n = 0;
+        for y in range_y {
+            for x in range_x.clone() {
+                if !self.cell(x, y).is_empty() {
+                    n += 1;
+                }
+            }
+        }
+        n
+    }
+
+    /// Return ys of all completely filled rows in ascending order.
+    pub fn filled_row_indices(&self) -> Vec<usize> {
+        (0..self.num_rows)
+            .filter(|y| self.is_row_filled(*y))
+            .collect()
+    }
+
+    pub fn num_filled_rows(&self) -> usize {
+        self.filled_row_indices().len()
+    }
+
+    pub fn is_col_filled(&self, x: usize) -> bool {
+        for y in 0..self.num_rows {
             if self.cell(x, y).is_empty() {
                 return false;
             }
@@ -194,16 +520,100 @@ where
         true
     }
 
-    pub fn num_filled_rows(&self) -> usize {
+    pub fn num_filled_cols(&self) -> usize {
         let mut n = 0;
-        for y in 0..self.num_rows {
-            if self.is_row_filled(y) {
+        for x in 0..self.num_cols {
+            if self.is_col_filled(x) {
                 n += 1;
             }
         }
         n
     }
 
+    /// Return the height of each column, i.e. y of the highest non-empty cell + 1.
+    pub fn column_heights(&self) -> Vec<usize> {
+        let mut heights = Vec::with_capacity(self.num_cols);
+        for x in 0..self.num_cols {
+            let mut h = 0;
+            for y in (0..self.num_rows).rev() {
+                if !self.cell(x, y).is_empty() {
+                    h = y + 1;
+                    break;
+                }
+            }
+            heights.push(h);
+        }
+        heights
+    }
+
+    /// Return the number of non-empty cells whose upper cell is empty.
+    /// Cells in the top row are regarded as exposed.
+    pub fn surface_cells(&self) -> usize {
+        self.iter_filled()
+            .filter(|(x, y, _)| y + 1 == self.num_rows || self.cell(*x, y + 1).is_empty())
+            .count()
+    }
+
+    /// Return the number of empty cells with any non-empty cell above in the same column.
+    pub fn covered_empty_cells(&self) -> usize {
+        self.column_heights()
+            .iter()
+            .enumerate()
+            .map(|(x, h)| (0..*h).filter(|y| self.cell(x, *y).is_empty()).count())
+            .sum()
+    }
+
+    /// Return the column index of the deepest well and its depth, where the depth is measured
+    /// from the lower neighbor. The walls are regarded as infinitely high.
+    /// Return None if there are no wells.
+    pub fn deepest_well(&self) -> Option<(usize, usize)> {
+        let heights = self.column_heights();
+        let mut r: Option<(usize, usize)> = None;
+        for (x, h) in heights.iter().enumerate() {
+            let left = if x == 0 { None } else { Some(heights[x - 1]) };
+            let right = heights.get(x + 1).copied();
+            let neighbor = match (left, right) {
+                (Some(l), Some(r)) => l.min(r),
+                (Some(l), None) => l,
+                (None, Some(r)) => r,
+                (None, None) => continue,
+            };
+            if neighbor <= *h {
+                continue;
+            }
+            let depth = neighbor - h;
+            if r.map_or(true, |(_, d)| d < depth) {
+                r = Some((x, depth));
+            }
+        }
+        r
+    }
+
+    /// Return the sum of the cumulative well depths, the well feature of Dellacherie's algorithm.
+    /// A well cell is an empty cell whose left and right cells are non-empty or walls,
+    /// and a well of depth d counts 1 + 2 + ... + d.
+    pub fn cumulative_wells(&self) -> usize {
+        let is_wall_or_filled = |x: i32, y: usize| {
+            x < 0 || x as usize >= self.num_cols || !self.cell(x as usize, y).is_empty()
+        };
+        let mut sum = 0;
+        for x in 0..self.num_cols {
+            let mut depth = 0;
+            for y in (0..self.num_rows).rev() {
+                let is_well = self.cell(x, y).is_empty()
+                    && is_wall_or_filled(x as i32 - 1, y)
+                    && is_wall_or_filled(x as i32 + 1, y);
+                if is_well {
+                    depth += 1;
+                    sum += depth;
+                } else {
+                    depth = 0;
+                }
+            }
+        }
+        sum
+    }
+
     pub fn pluck_filled_rows(&mut self, placeholder: Option<C>) -> usize {
         let mut n = 0;
         for y in 0..self.num_rows {
@@ -224,58 +634,75 @@ where
         n
     }
 
-    pub fn check_overlay(&self, x: i32, y: i32, sub: &Grid<C>) -> OverlayResult {
-        let mut result = OverlayResult::empty();
-        for sub_y in 0..sub.num_rows {
-            for sub_x in 0..sub.num_cols {
-                let sub_cell = sub.cell(sub_x, sub_y);
-                if sub_cell.is_empty() {
-                    continue;
-                }
-                let self_x = x + sub_x as i32;
-                let self_y = y + sub_y as i32;
-                if self_x < 0
-                    || self.num_cols as i32 <= self_x
-                    || self_y < 0
-                    || self.num_rows as i32 <= self_y
-                {
-                    result |= OverlayResult::OVERFLOW;
+    /// Drop each non-empty cell individually until it lands on another cell or the bottom,
+    /// then return whether any cell moved.
+    pub fn apply_cascade_gravity(&mut self) -> bool {
+        let mut moved = false;
+        for x in 0..self.num_cols {
+            let mut dst_y = 0;
+            for y in 0..self.num_rows {
+                if self.cell(x, y).is_empty() {
                     continue;
                 }
-                let self_cell = self.cell(self_x as usize, self_y as usize);
-                if !self_cell.is_empty() {
-                    result |= OverlayResult::OVERLAP;
+                if y != dst_y {
+                    let cell = self.cell(x, y);
+                    self.set_cell(x, dst_y, cell);
+                    self.set_cell(x, y, C::default());
+                    moved = true;
                 }
+                dst_y += 1;
             }
         }
-        result
+        moved
+    }
+
+    pub fn check_overlay(&self, x: i32, y: i32, sub: &Grid<C>) -> OverlayResult {
+        self.check_overlay_detailed(x, y, sub).result
+    }
+
+    /// Same as `check_overlay()` but also return where the first overlap and overflow occurred.
+    pub fn check_overlay_detailed(&self, x: i32, y: i32, sub: &Grid<C>) -> OverlayDetail {
+        let mut detail = OverlayDetail::default();
+        for (sub_x, sub_y, _) in sub.iter_filled() {
+            let self_x = x + sub_x as i32;
+            let self_y = y + sub_y as i32;
+            if self_x < 0
+                || self.num_cols as i32 <= self_x
+                || self_y < 0
+                || self.num_rows as i32 <= self_y
+            {
+                detail.result |= OverlayResult::OVERFLOW;
+                detail.overflow.get_or_insert((self_x, self_y));
+                continue;
+            }
+            let self_cell = self.cell(self_x as usize, self_y as usize);
+            if !self_cell.is_empty() {
+                detail.result |= OverlayResult::OVERLAP;
+                detail.overlap.get_or_insert((self_x, self_y));
+            }
+        }
+        detail
     }
 
     pub fn overlay(&mut self, x: i32, y: i32, sub: &Grid<C>) -> OverlayResult {
         let mut result = OverlayResult::empty();
-        for sub_y in 0..sub.num_rows {
-            for sub_x in 0..sub.num_cols {
-                let sub_cell = sub.cell(sub_x, sub_y);
-                if sub_cell.is_empty() {
-                    continue;
-                }
-                let self_x = x + sub_x as i32;
-                let self_y = y + sub_y as i32;
-                if self_x < 0
-                    || self.num_cols as i32 <= self_x
-                    || self_y < 0
-                    || self.num_rows as i32 <= self_y
-                {
-                    result |= OverlayResult::OVERFLOW;
-                    continue;
-                }
-                let self_cell = self.cell(self_x as usize, self_y as usize);
-                if !self_cell.is_empty() {
-                    result |= OverlayResult::OVERLAP;
-                } else {
-                    // NOTE: completely same code as check_overlay() except here
-                    self.set_cell(self_x as usize, self_y as usize, sub_cell);
-                }
+        for (sub_x, sub_y, sub_cell) in sub.iter_filled() {
+            let self_x = x + sub_x as i32;
+            let self_y = y + sub_y as i32;
+            if self_x < 0
+                || self.num_cols as i32 <= self_x
+                || self_y < 0
+                || self.num_rows as i32 <= self_y
+            {
+                result |= OverlayResult::OVERFLOW;
+                continue;
+            }
+            let self_cell = self.cell(self_x as usize, self_y as usize);
+            if !self_cell.is_empty() {
+                result |= OverlayResult::OVERLAP;
+            } else {
+                // NOTE: completely same code as check_overlay() except here
+                self.set_cell(self_x as usize, self_y as usize, sub_cell.clone());
             }
         }
         result
@@ -330,31 +757,70 @@ where
         }
         self.num_rows()
     }
+
+    pub fn left_padding(&self) -> usize {
+        for x in 0..self.num_cols {
+            for y in 0..self.num_rows {
+                if !self.cell(x, y).is_empty() {
+                    return x;
+                }
+            }
+        }
+        self.num_cols()
+    }
+
+    pub fn right_padding(&self) -> usize {
+        for n in 0..self.num_cols {
+            let x = self.num_cols - n - 1;
+            for y in 0..self.num_rows {
+                if !self.cell(x, y).is_empty() {
+                    return n;
+                }
+            }
+        }
+        self.num_cols()
+    }
+
+    /// Return y above the highest non-empty cell.
+    pub fn significant_height(&self) -> usize {
+        self.num_rows - self.top_padding()
+    }
+
+    /// Return the grid without empty top rows.
+    pub fn trimmed(&self) -> Grid<C> {
+        let h = self.significant_height();
+        Grid::new(self.num_cols, h, self.cells[..(h * self.num_cols)].to_vec())
+    }
 }
 
 //---
 
-pub struct GridFormatOptions {
+pub type CellFormatFn<C> = dyn Fn(&C) -> String;
+
+pub struct GridFormatOptions<C> {
     pub str_begin_of_line: &'static str,
     pub str_end_of_line: &'static str,
     pub range_x: Option<Range<usize>>,
     pub range_y: Option<Range<usize>>,
+    /// If set, used to format each cell instead of `Display`.
+    pub cell_fmt: Option<Box<CellFormatFn<C>>>,
 }
 
-impl Default for GridFormatOptions {
+impl<C> Default for GridFormatOptions<C> {
     fn default() -> Self {
         Self {
             str_begin_of_line: "",
             str_end_of_line: "",
             range_x: Option::None,
             range_y: Option::None,
+            cell_fmt: Option::None,
         }
     }
 }
 
 pub struct GridFormatter<'a, C> {
     pub grid: &'a Grid<C>,
-    pub opts: GridFormatOptions,
+    pub opts: GridFormatOptions<C>,
 }
 
 impl<'a, C> fmt::Display for GridFormatter<'a, C>
@@ -375,8 +841,13 @@ where
             if let Err(r) = formatter.write_str(self.opts.str_begin_of_line) {
                 return Err(r);
             }
-            for x in range_x.clone() {
-                if let Err(r) = self.grid.cell(x, y).fmt(formatter) {
+            for cell in &self.grid.row_slice(y)[range_x.clone()] {
+                let r = if let Some(cell_fmt) = self.opts.cell_fmt.as_ref() {
+                    formatter.write_str(&cell_fmt(cell))
+                } else {
+                    cell.fmt(formatter)
+                };
+                if let Err(r) = r {
                     return Err(r);
                 }
             }
@@ -451,8 +922,25 @@ mod tests {
         assert_eq!(OverlayResult::OVERFLOW, r);
 
         let r = grid.check_overlay(3, 3, &sub);
-        assert!(r.contains(OverlayResult::OVERFLOW));
-        assert!(r.contains(OverlayResult::OVERLAP));
+        assert!(r.is_overflow());
+        assert!(r.is_overlap());
+
+        assert_eq!(
+            OverlayDetail {
+                result: OverlayResult::OVERLAP,
+                overlap: Some((1, 1)),
+                overflow: None,
+            },
+            grid.check_overlay_detailed(0, 0, &sub)
+        );
+        assert_eq!(
+            OverlayDetail {
+                result: OverlayResult::OVERFLOW | OverlayResult::OVERLAP,
+                overlap: Some((3, 3)),
+                overflow: Some((4, 4)),
+            },
+            grid.check_overlay_detailed(3, 3, &sub)
+        );
 
         assert_eq!(
             (0, OverlayResult::OVERLAP),
@@ -473,6 +961,35 @@ mod tests {
         assert_eq!(1, grid.cell(1, 2));
     }
 
+    #[test]
+    fn apply_cascade_gravity_test() {
+        let mut grid = MyGrid::new(
+            3,
+            4,
+            vec![
+                0, 4, 0, //
+                3, 0, 0, //
+                0, 2, 0, //
+                1, 0, 0, //
+            ],
+        );
+        grid.reverse_rows();
+        assert!(grid.apply_cascade_gravity());
+        let mut expected = MyGrid::new(
+            3,
+            4,
+            vec![
+                0, 0, 0, //
+                0, 0, 0, //
+                3, 4, 0, //
+                1, 2, 0, //
+            ],
+        );
+        expected.reverse_rows();
+        assert!(expected == grid);
+        assert!(!grid.apply_cascade_gravity());
+    }
+
     #[test]
     fn padding_test() {
         let mut grid = MyGrid::new(
@@ -489,10 +1006,458 @@ mod tests {
         grid.reverse_rows();
         assert_eq!(1, grid.top_padding());
         assert_eq!(2, grid.bottom_padding());
+        assert_eq!(0, grid.left_padding());
+        assert_eq!(0, grid.right_padding());
 
         let grid = MyGrid::new(1, 2, vec![]);
         assert_eq!(2, grid.top_padding());
         assert_eq!(2, grid.bottom_padding());
+        assert_eq!(1, grid.left_padding());
+        assert_eq!(1, grid.right_padding());
+
+        let mut grid = MyGrid::new(5, 1, vec![]);
+        grid.set_cell(1, 0, 1);
+        grid.set_cell(2, 0, 1);
+        assert_eq!(1, grid.left_padding());
+        assert_eq!(2, grid.right_padding());
+    }
+
+    #[test]
+    fn trimmed_test() {
+        let mut grid = MyGrid::new(3, 10, vec![]);
+        grid.set_cell(0, 0, 1);
+        grid.set_cell(1, 2, 2);
+        grid.set_cell(2, 3, 3);
+        assert_eq!(4, grid.significant_height());
+        let trimmed = grid.trimmed();
+        assert_eq!(3, trimmed.num_cols());
+        assert_eq!(4, trimmed.num_rows());
+        assert_eq!(1, trimmed.cell(0, 0));
+        assert_eq!(2, trimmed.cell(1, 2));
+        assert_eq!(3, trimmed.cell(2, 3));
+
+        let grid = MyGrid::new(3, 10, vec![]);
+        assert_eq!(0, grid.significant_height());
+        assert_eq!(0, grid.trimmed().num_rows());
+    }
+
+    #[test]
+    fn filled_cols_test() {
+        let mut grid = MyGrid::new(
+            3,
+            3,
+            vec![
+                0, 1, 0, //
+                1, 1, 0, //
+                0, 1, 1, //
+            ],
+        );
+        grid.reverse_rows();
+        assert!(!grid.is_col_filled(0));
+        assert!(grid.is_col_filled(1));
+        assert!(!grid.is_col_filled(2));
+        assert_eq!(1, grid.num_filled_cols());
+        assert_eq!(0, grid.num_filled_rows());
+        assert_eq!(vec![2, 3, 1], grid.column_heights());
+    }
+
+    #[test]
+    fn row_mask_test() {
+        let mut grid = MyGrid::new(
+            3,
+            4,
+            vec![
+                1, 1, 1, //
+                0, 1, 0, //
+                1, 1, 1, //
+                1, 0, 1, //
+            ],
+        );
+        grid.reverse_rows();
+        assert_eq!(vec![true, false, true], grid.row_mask(0));
+        assert_eq!(vec![true, true, true], grid.row_mask(1));
+        assert_eq!(vec![false, true, false], grid.row_mask(2));
+        assert!(grid.is_row_filled(1));
+        assert!(!grid.is_row_filled(2));
+        assert_eq!(vec![1, 3], grid.filled_row_indices());
+        assert_eq!(2, grid.num_filled_rows());
+    }
+
+    #[test]
+    fn overlay_result_helpers() {
+        let r = OverlayResult::empty();
+        assert_eq!(
+            (false, false, false),
+            (r.is_overflow(), r.is_overlap(), r.blocks_placement())
+        );
+        let r = OverlayResult::OVERFLOW;
+        assert_eq!(
+            (true, false, true),
+            (r.is_overflow(), r.is_overlap(), r.blocks_placement())
+        );
+        let r = OverlayResult::OVERLAP;
+        assert_eq!(
+            (false, true, true),
+            (r.is_overflow(), r.is_overlap(), r.blocks_placement())
+        );
+        let r = OverlayResult::OVERFLOW | OverlayResult::OVERLAP;
+        assert_eq!(
+            (true, true, true),
+            (r.is_overflow(), r.is_overlap(), r.blocks_placement())
+        );
+    }
+
+    #[test]
+    fn iter_filled_test() {
+        let mut grid = MyGrid::new(4, 3, vec![]);
+        grid.set_cell(3, 0, 1);
+        grid.set_cell(0, 2, 2);
+        grid.set_cell(1, 2, 3);
+        let filled: Vec<_> = grid.iter_filled().map(|(x, y, c)| (x, y, *c)).collect();
+        assert_eq!(vec![(3, 0, 1), (0, 2, 2), (1, 2, 3)], filled);
+        assert_eq!(0, MyGrid::new(4, 3, vec![]).iter_filled().count());
+    }
+
+    #[test]
+    fn from_occupied_test() {
+        // L
+        let grid = MyGrid::from_occupied(3, 3, &[(0, 0), (1, 0), (2, 0), (2, 1)], 1);
+        let expected = MyGrid::from_rows(vec![vec![0, 0, 0], vec![0, 0, 1], vec![1, 1, 1]]);
+        assert_eq!(expected, grid);
+        assert_eq!(4, grid.count_filled_in(0..3, 0..3));
+    }
+
+    #[test]
+    fn rows_test() {
+        let grid = MyGrid::from_rows(vec![vec![1, 2], vec![3, 4], vec![5, 6]]);
+        assert_eq!(grid.num_rows(), grid.rows().count());
+        assert_eq!(Some(&[5, 6][..]), grid.rows().next());
+        let rows: Vec<_> = grid.rows_top_down().collect();
+        assert_eq!(vec![&[1, 2][..], &[3, 4], &[5, 6]], rows);
+        assert_eq!(0, MyGrid::new(3, 0, vec![]).rows().count());
+    }
+
+    #[test]
+    fn cols_test() {
+        let grid = MyGrid::from_rows(vec![vec![1, 2], vec![3, 4], vec![5, 6]]);
+        assert_eq!(grid.num_cols(), grid.cols().count());
+        assert_eq!(Some(vec![5, 3, 1]), grid.cols().next());
+        assert_eq!(Some(vec![6, 4, 2]), grid.cols().last());
+    }
+
+    #[test]
+    fn map_cells_test() {
+        let grid = MyGrid::from_rows(vec![vec![0, 2], vec![1, 0]]);
+        let mask = grid.map_cells(|c| *c != 0);
+        assert_eq!(
+            Grid::from_rows(vec![vec![false, true], vec![true, false]]),
+            mask
+        );
+    }
+
+    #[test]
+    fn iter_mut_cells_test() {
+        let mut grid = MyGrid::from_rows(vec![vec![1, 2, 3], vec![4, 5, 6]]);
+        for (x, _, c) in grid.iter_mut_cells() {
+            if x % 2 == 1 {
+                *c = 0;
+            }
+        }
+        assert_eq!(MyGrid::from_rows(vec![vec![1, 0, 3], vec![4, 0, 6]]), grid);
+    }
+
+    #[test]
+    fn try_cell_test() {
+        let mut grid = MyGrid::new(3, 2, vec![]);
+        assert_eq!(Ok(()), grid.try_set_cell(2, 1, 5));
+        assert_eq!(Some(5), grid.try_cell(2, 1));
+        assert_eq!(Some(0), grid.try_cell(0, 0));
+        assert_eq!(Err(OutOfBounds { x: 3, y: 0 }), grid.try_set_cell(3, 0, 1));
+        assert_eq!(Err(OutOfBounds { x: 0, y: 2 }), grid.try_set_cell(0, 2, 1));
+        assert_eq!(None, grid.try_cell(3, 0));
+        assert_eq!(None, grid.try_cell(0, 2));
+        assert_eq!(1, grid.count_filled_in(0..3, 0..2));
+    }
+
+    #[test]
+    fn equals_by_test() {
+        // Treat 9 as empty.
+        let eq = |a: &MyCell, b: &MyCell| a % 9 == b % 9;
+        let a = MyGrid::new(2, 2, vec![1, 0, 9, 2]);
+        let b = MyGrid::new(2, 2, vec![1, 9, 0, 2]);
+        assert!(a != b);
+        assert!(a.equals_by(&b, eq));
+        assert!(!a.equals_by(&MyGrid::new(2, 2, vec![1, 0, 0, 3]), eq));
+        assert!(!a.equals_by(&MyGrid::new(4, 1, vec![1, 0, 9, 2]), eq));
+    }
+
+    #[test]
+    fn hamming_distance_test() {
+        let a = MyGrid::new(3, 3, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        let b = MyGrid::new(3, 3, vec![1, 0, 3, 4, 5, 0, 0, 8, 9]);
+        assert_eq!(0, a.hamming_distance(&a));
+        assert_eq!(3, a.hamming_distance(&b));
+        assert_eq!(3, b.hamming_distance(&a));
+    }
+
+    #[test]
+    #[should_panic]
+    fn hamming_distance_size_mismatch() {
+        MyGrid::new(3, 3, vec![]).hamming_distance(&MyGrid::new(3, 2, vec![]));
+    }
+
+    #[test]
+    fn set_row_test() {
+        let mut grid = MyGrid::new(3, 2, vec![]);
+        grid.set_row(1, &[1, 0, 3]);
+        assert_eq!(1, grid.cell(0, 1));
+        assert_eq!(0, grid.cell(1, 1));
+        assert_eq!(3, grid.cell(2, 1));
+        assert_eq!(0, grid.count_filled_in(0..3, 0..1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn set_row_wrong_width() {
+        let mut grid = MyGrid::new(3, 2, vec![]);
+        grid.set_row(0, &[1, 2]);
+    }
+
+    #[test]
+    fn crop_test() {
+        let mut grid = MyGrid::new(
+            3,
+            3,
+            vec![
+                1, 2, 3, //
+                4, 5, 6, //
+                7, 8, 9, //
+            ],
+        );
+        grid.reverse_rows();
+        let mut expected = MyGrid::new(
+            2,
+            2,
+            vec![
+                5, 6, //
+                8, 9, //
+            ],
+        );
+        expected.reverse_rows();
+        assert!(expected == grid.crop(1..3, 0..2));
+        assert!(expected == grid.crop(1..5, 0..2));
+        let empty = grid.crop(3..5, 0..2);
+        assert_eq!((0, 2), (empty.num_cols(), empty.num_rows()));
+    }
+
+    #[test]
+    fn paste_region_test() {
+        let mut src = MyGrid::new(
+            3,
+            3,
+            vec![
+                1, 2, 3, //
+                4, 0, 6, //
+                7, 8, 9, //
+            ],
+        );
+        src.reverse_rows();
+        let mut grid = MyGrid::new(4, 4, vec![5; 16]);
+        grid.paste_region((1, 1), &src, 1..3, 0..2);
+        let mut expected = MyGrid::new(
+            4,
+            4,
+            vec![
+                5, 5, 5, 5, //
+                5, 0, 6, 5, //
+                5, 8, 9, 5, //
+                5, 5, 5, 5, //
+            ],
+        );
+        expected.reverse_rows();
+        assert_eq!(expected, grid);
+
+        // clipping
+        let mut grid = MyGrid::new(4, 4, vec![5; 16]);
+        grid.paste_region((3, 3), &src, 0..10, 0..10);
+        let mut expected = MyGrid::new(4, 4, vec![5; 16]);
+        expected.set_cell(3, 3, 7);
+        assert_eq!(expected, grid);
+    }
+
+    #[test]
+    fn deepest_well_test() {
+        let grid = MyGrid::from_rows(vec![
+            vec![0, 0, 0, 0, 0, 0],
+            vec![1, 1, 0, 1, 1, 0],
+            vec![1, 1, 0, 1, 1, 0],
+            vec![1, 1, 0, 1, 1, 1],
+            vec![1, 1, 0, 1, 1, 1],
+            vec![1, 0, 1, 1, 1, 1],
+        ]);
+        assert_eq!(Some((2, 4)), grid.deepest_well());
+        assert_eq!(None, MyGrid::new(3, 3, vec![]).deepest_well());
+    }
+
+    #[test]
+    fn surface_and_covered_cells_test() {
+        let grid = MyGrid::from_rows(vec![
+            vec![0, 0, 0, 0],
+            vec![1, 1, 0, 0],
+            vec![0, 1, 0, 1],
+            vec![0, 0, 1, 1],
+        ]);
+        assert_eq!(4, grid.surface_cells());
+        assert_eq!(3, grid.covered_empty_cells());
+        let grid = MyGrid::from_rows(vec![vec![1, 1], vec![1, 1]]);
+        assert_eq!(2, grid.surface_cells());
+        assert_eq!(0, grid.covered_empty_cells());
+    }
+
+    #[test]
+    fn cumulative_wells_test() {
+        let grid = MyGrid::from_rows(vec![
+            vec![0, 0, 0, 0, 0, 0],
+            vec![0, 1, 0, 1, 1, 1],
+            vec![0, 1, 0, 1, 1, 1],
+            vec![1, 1, 0, 1, 1, 1],
+            vec![1, 1, 0, 1, 1, 1],
+        ]);
+        // Wells at x=2 (depth 4) and x=0 (depth 2 along the wall).
+        assert_eq!((1 + 2 + 3 + 4) + (1 + 2), grid.cumulative_wells());
+        assert_eq!(0, MyGrid::new(3, 3, vec![]).cumulative_wells());
+    }
+
+    #[test]
+    fn count_filled_in_test() {
+        let grid = MyGrid::from_rows(vec![vec![1, 0, 0, 1], vec![0, 1, 1, 0], vec![1, 1, 0, 1]]);
+        assert_eq!(7, grid.count_filled_in(0..4, 0..3));
+        assert_eq!(3, grid.count_filled_in(1..3, 0..2));
+        assert_eq!(0, grid.count_filled_in(1..3, 2..3));
+        assert_eq!(0, grid.count_filled_in(2..2, 0..3));
+    }
+
+    #[test]
+    #[should_panic]
+    fn count_filled_in_out_of_bounds() {
+        MyGrid::new(3, 3, vec![]).count_filled_in(0..4, 0..3);
+    }
+
+    #[test]
+    fn shift_rows_test() {
+        let grid = MyGrid::from_rows(vec![
+            vec![6, 6],
+            vec![5, 5],
+            vec![4, 4],
+            vec![3, 3],
+            vec![2, 2],
+            vec![1, 1],
+        ]);
+        let mut up = grid.clone();
+        up.shift_rows(0..3, 2, Some(9));
+        let expected = MyGrid::from_rows(vec![
+            vec![6, 6],
+            vec![3, 3],
+            vec![2, 2],
+            vec![1, 1],
+            vec![9, 9],
+            vec![9, 9],
+        ]);
+        assert_eq!(expected, up);
+
+        let mut up = grid.clone();
+        up.shift_rows(0..3, 2, None);
+        let expected = MyGrid::from_rows(vec![
+            vec![6, 6],
+            vec![3, 3],
+            vec![2, 2],
+            vec![1, 1],
+            vec![2, 2],
+            vec![1, 1],
+        ]);
+        assert_eq!(expected, up);
+
+        let mut down = grid;
+        down.shift_rows(4..6, -5, Some(0));
+        let expected = MyGrid::from_rows(vec![
+            vec![0, 0],
+            vec![0, 0],
+            vec![4, 4],
+            vec![3, 3],
+            vec![2, 2],
+            vec![6, 6],
+        ]);
+        assert_eq!(expected, down);
+    }
+
+    #[test]
+    fn shift_test() {
+        let mut grid = MyGrid::new(
+            3,
+            3,
+            vec![
+                1, 2, 3, //
+                4, 5, 6, //
+                7, 8, 9, //
+            ],
+        );
+        grid.reverse_rows();
+
+        let mut up = grid.clone();
+        up.shift(0, 1);
+        let mut expected = MyGrid::new(
+            3,
+            3,
+            vec![
+                4, 5, 6, //
+                7, 8, 9, //
+                0, 0, 0, //
+            ],
+        );
+        expected.reverse_rows();
+        assert_eq!(expected, up);
+
+        let mut down = grid.clone();
+        down.shift(0, -1);
+        let mut expected = MyGrid::new(
+            3,
+            3,
+            vec![
+                0, 0, 0, //
+                1, 2, 3, //
+                4, 5, 6, //
+            ],
+        );
+        expected.reverse_rows();
+        assert_eq!(expected, down);
+
+        let mut left = grid.clone();
+        left.shift(-1, 0);
+        let mut expected = MyGrid::new(
+            3,
+            3,
+            vec![
+                2, 3, 0, //
+                5, 6, 0, //
+                8, 9, 0, //
+            ],
+        );
+        expected.reverse_rows();
+        assert_eq!(expected, left);
+
+        let mut right = grid.clone();
+        right.shift(1, 0);
+        let mut expected = MyGrid::new(
+            3,
+            3,
+            vec![
+                0, 1, 2, //
+                0, 4, 5, //
+                0, 7, 8, //
+            ],
+        );
+        expected.reverse_rows();
+        assert_eq!(expected, right);
     }
 
     #[test]
@@ -501,6 +1466,29 @@ mod tests {
         assert_eq!(grid, grid.clone());
     }
 
+    #[test]
+    fn from_rows_test() {
+        let mut expected = MyGrid::new(
+            3,
+            2,
+            vec![
+                1, 2, 3, //
+                4, 5, 6, //
+            ],
+        );
+        expected.reverse_rows();
+        let grid = MyGrid::from_rows(vec![vec![1, 2, 3], vec![4, 5, 6]]);
+        assert_eq!(expected, grid);
+        assert_eq!(4, grid.cell(0, 0));
+        assert_eq!(3, grid.cell(2, 1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_rows_inconsistent_width() {
+        MyGrid::from_rows(vec![vec![1, 2, 3], vec![4, 5]]);
+    }
+
     #[test]
     fn rotate_test() {
         let mut grid = MyGrid::new(
@@ -548,6 +1536,17 @@ mod tests {
         assert_eq!(expected1, grid.rotate1());
         assert_eq!(expected2, grid.rotate2());
         assert_eq!(expected3, grid.rotate3());
+
+        let mut flipped = grid.clone();
+        flipped.flip180();
+        assert_eq!(expected2, flipped);
+        flipped.flip180();
+        assert_eq!(grid, flipped);
+
+        assert_eq!(grid, grid.rotate(Rotation::Cw0));
+        assert_eq!(grid.rotate1(), grid.rotate(Rotation::Cw90));
+        assert_eq!(grid.rotate2(), grid.rotate(Rotation::Cw180));
+        assert_eq!(grid.rotate3(), grid.rotate(Rotation::Cw270));
     }
 
     #[test]
@@ -575,6 +1574,23 @@ mod tests {
                         str_end_of_line: "E",
                         range_x: Some(0..1),
                         range_y: Some(1..2),
+                        cell_fmt: None,
+                    }
+                },
+            ),
+        );
+        grid.set_cell(0, 0, 0);
+        assert_eq!(
+            "##\n##\n.#\n",
+            format!(
+                "{}",
+                GridFormatter::<MyCell> {
+                    grid: &grid,
+                    opts: GridFormatOptions {
+                        cell_fmt: Some(Box::new(|c| {
+                            if c.is_empty() { "." } else { "#" }.into()
+                        })),
+                        ..Default::default()
                     }
                 },
             ),
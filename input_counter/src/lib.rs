@@ -1,8 +1,27 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 extern crate num_traits;
 
+use alloc::vec::Vec;
 use num_traits::NumAssign;
-use std::collections::HashMap;
-use std::hash::Hash;
+
+/// The map of `InputManager`, `HashMap` with the std feature and `BTreeMap` without.
+#[cfg(feature = "std")]
+pub type InputMap<K, V> = std::collections::HashMap<K, V>;
+#[cfg(not(feature = "std"))]
+pub type InputMap<K, V> = alloc::collections::BTreeMap<K, V>;
+
+/// Requirements for the inputs of `InputManager`, `Eq + Hash` with the std feature and `Ord`
+/// without.
+#[cfg(feature = "std")]
+pub trait InputKey: Eq + core::hash::Hash {}
+#[cfg(feature = "std")]
+impl<T: Eq + core::hash::Hash> InputKey for T {}
+#[cfg(not(feature = "std"))]
+pub trait InputKey: Ord {}
+#[cfg(not(feature = "std"))]
+impl<T: Ord> InputKey for T {}
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum InputState {
@@ -12,7 +31,7 @@ pub enum InputState {
     End,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct InputCounter<Num = u8> {
     opt_repeat: Num,
     opt_first_delay: Num,
@@ -20,10 +39,23 @@ pub struct InputCounter<Num = u8> {
     can_handle: bool,
     is_handled: bool,
     is_repeating: bool,
+    /// The number of remaining handles in the current frame.
+    num_handles: u32,
     n: Num,
 }
 
-impl<Num: NumAssign + Copy> InputCounter<Num> {
+/// The variable part of `InputCounter`, cf. `InputCounter::snapshot()`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct InputCounterState<Num = u8> {
+    state: InputState,
+    n: Num,
+    num_handles: u32,
+    can_handle: bool,
+    is_handled: bool,
+    is_repeating: bool,
+}
+
+impl<Num: NumAssign + Copy + PartialOrd> InputCounter<Num> {
     pub fn new(repeat: Num, first_delay: Num) -> Self {
         Self {
             opt_repeat: repeat,
@@ -36,15 +68,29 @@ impl<Num: NumAssign + Copy> InputCounter<Num> {
             can_handle: false,
             is_handled: false,
             is_repeating: false,
+            num_handles: 0,
             n: Num::zero(),
         }
     }
+    /// Handled once per press.
+    pub fn one_shot() -> Self {
+        Self::new(Num::zero(), Num::zero())
+    }
+    /// Handled once at the press, then after `das` frames, then every `arr` frames.
+    pub fn auto_repeat(das: Num, arr: Num) -> Self {
+        Self::new(arr, das)
+    }
+    /// Handled every frame while pressed.
+    pub fn per_frame() -> Self {
+        Self::new(Num::one(), Num::zero())
+    }
     pub fn update(&mut self, active: bool) {
         if !active {
             self.state = InputState::Inactive;
             self.can_handle = false;
             self.is_handled = false;
             self.is_repeating = false;
+            self.num_handles = 0;
             self.n = Num::zero();
             return;
         }
@@ -55,6 +101,7 @@ impl<Num: NumAssign + Copy> InputCounter<Num> {
         match self.state {
             InputState::Inactive => {
                 self.can_handle = true;
+                self.num_handles = 1;
                 self.state = if self.opt_repeat.is_zero() {
                     InputState::End
                 } else {
@@ -63,27 +110,48 @@ impl<Num: NumAssign + Copy> InputCounter<Num> {
             }
             InputState::Delay => {
                 self.n += Num::one();
-                self.can_handle = self.n == self.opt_first_delay;
+                self.can_handle = self.n >= self.opt_first_delay;
                 if self.can_handle {
-                    self.n = Num::zero();
+                    // Carry the fractional part over to the repeat.
+                    self.n -= self.opt_first_delay;
+                    self.num_handles = 1;
                     self.state = InputState::Repeat;
                 }
             }
             InputState::Repeat => {
-                self.n = (self.n + Num::one()) % self.opt_repeat;
-                self.can_handle = self.n.is_zero();
+                // `opt_repeat` can be less than one frame, e.g. 0.5, then handled multiple times.
+                self.n += Num::one();
+                self.num_handles = 0;
+                while self.n >= self.opt_repeat {
+                    self.n -= self.opt_repeat;
+                    self.num_handles += 1;
+                }
+                self.can_handle = self.num_handles > 0;
             }
             InputState::End => {
                 // do nothing
             }
         }
     }
+    /// Same as calling `update(active)` `frames` times, where `frames` is a whole number.
+    /// Stops early once handleable since `update` waits for `handle` from then on.
+    pub fn update_by(&mut self, active: bool, frames: Num) {
+        let mut i = Num::zero();
+        while i < frames {
+            self.update(active);
+            if self.can_handle {
+                break;
+            }
+            i += Num::one();
+        }
+    }
     pub fn can_handle(&self) -> bool {
         self.can_handle
     }
     pub fn handle(&mut self) -> bool {
         if self.can_handle {
-            self.can_handle = false;
+            self.num_handles = self.num_handles.saturating_sub(1);
+            self.can_handle = self.num_handles > 0;
             self.is_handled = true;
             self.is_repeating = self.state != InputState::End;
             return true;
@@ -93,6 +161,28 @@ impl<Num: NumAssign + Copy> InputCounter<Num> {
     pub fn is_repeating(&self) -> bool {
         self.is_repeating
     }
+    pub fn state(&self) -> InputState {
+        self.state
+    }
+    /// Return the current state without the options.
+    pub fn snapshot(&self) -> InputCounterState<Num> {
+        InputCounterState {
+            state: self.state,
+            n: self.n,
+            num_handles: self.num_handles,
+            can_handle: self.can_handle,
+            is_handled: self.is_handled,
+            is_repeating: self.is_repeating,
+        }
+    }
+    pub fn restore(&mut self, s: InputCounterState<Num>) {
+        self.state = s.state;
+        self.n = s.n;
+        self.num_handles = s.num_handles;
+        self.can_handle = s.can_handle;
+        self.is_handled = s.is_handled;
+        self.is_repeating = s.is_repeating;
+    }
 }
 
 pub trait Contains<T> {
@@ -100,12 +190,27 @@ pub trait Contains<T> {
 }
 
 #[derive(Debug, Clone, Default)]
-pub struct InputManager<Input: Eq + Hash, Num> {
-    inputs: HashMap<Input, InputCounter<Num>>,
+pub struct InputManager<Input: InputKey, Num> {
+    inputs: InputMap<Input, InputCounter<Num>>,
 }
 
-impl<Input: Eq + Hash + Clone, Num: NumAssign + Copy> InputManager<Input, Num> {
-    pub fn inputs(&self) -> &HashMap<Input, InputCounter<Num>> {
+/// Counter states of `InputManager` by input, e.g. for rollback.
+/// Reuse it with `InputManager::snapshot_into()` to avoid allocating per snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputManagerState<Input, Num> {
+    counters: Vec<(Input, InputCounterState<Num>)>,
+}
+
+impl<Input, Num> Default for InputManagerState<Input, Num> {
+    fn default() -> Self {
+        Self {
+            counters: Vec::new(),
+        }
+    }
+}
+
+impl<Input: InputKey + Clone, Num: NumAssign + Copy + PartialOrd> InputManager<Input, Num> {
+    pub fn inputs(&self) -> &InputMap<Input, InputCounter<Num>> {
         &self.inputs
     }
     pub fn register(
@@ -120,6 +225,11 @@ impl<Input: Eq + Hash + Clone, Num: NumAssign + Copy> InputManager<Input, Num> {
             c.update(inputs.contains(i.clone()));
         }
     }
+    pub fn update_by(&mut self, inputs: impl Contains<Input>, frames: Num) {
+        for (i, c) in &mut self.inputs {
+            c.update_by(inputs.contains(i.clone()), frames);
+        }
+    }
     pub fn can_handle(&self, input: Input) -> bool {
         if let Some(c) = self.inputs.get(&input) {
             c.can_handle()
@@ -141,6 +251,28 @@ impl<Input: Eq + Hash + Clone, Num: NumAssign + Copy> InputManager<Input, Num> {
             false
         }
     }
+    pub fn snapshot(&self) -> InputManagerState<Input, Num> {
+        let mut state = InputManagerState::default();
+        self.snapshot_into(&mut state);
+        state
+    }
+    /// Overwrite `state` with the current counter states, reusing its buffer.
+    pub fn snapshot_into(&self, state: &mut InputManagerState<Input, Num>) {
+        state.counters.clear();
+        state
+            .counters
+            .extend(self.inputs.iter().map(|(i, c)| (i.clone(), c.snapshot())));
+    }
+    /// Panics if `state` was taken from a manager with different inputs.
+    pub fn restore(&mut self, state: &InputManagerState<Input, Num>) {
+        assert_eq!(self.inputs.len(), state.counters.len());
+        for (i, s) in &state.counters {
+            self.inputs
+                .get_mut(i)
+                .expect("restoring an unregistered input")
+                .restore(*s);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -201,4 +333,194 @@ mod tests {
         c.update(true);
         assert!(c.handle());
     }
+    fn handled_frames(mut c: InputCounter, frames: usize) -> [bool; 8] {
+        let mut handled = [false; 8];
+        for h in handled.iter_mut().take(frames) {
+            c.update(true);
+            *h = c.handle();
+        }
+        handled
+    }
+
+    #[test]
+    fn presets() {
+        let (t, f) = (true, false);
+        assert_eq!(
+            [t, f, f, f, f, f, f, f],
+            handled_frames(InputCounter::one_shot(), 8)
+        );
+        assert_eq!(
+            [t, f, f, t, f, t, f, t],
+            handled_frames(InputCounter::auto_repeat(3, 2), 8)
+        );
+        assert_eq!(
+            [t, t, t, t, t, t, t, t],
+            handled_frames(InputCounter::per_frame(), 8)
+        );
+    }
+
+    struct Inputs<'a>(&'a [u8]);
+
+    impl Contains<u8> for Inputs<'_> {
+        fn contains(&self, v: u8) -> bool {
+            self.0.contains(&v)
+        }
+    }
+
+    #[test]
+    fn snapshot_restore() {
+        let mut mgr = InputManager::<u8, u8>::default();
+        mgr.register(0, InputCounter::auto_repeat(3, 2));
+        mgr.register(1, InputCounter::one_shot());
+        // Charge DAS of 0 while 1 is pending.
+        for _ in 0..3 {
+            mgr.update(Inputs(&[0, 1]));
+            mgr.handle(0);
+        }
+        let state = mgr.snapshot();
+        let expected = mgr.clone();
+        for _ in 0..5 {
+            mgr.update(Inputs(&[0]));
+            mgr.handle(0);
+        }
+        assert_ne!(expected.inputs(), mgr.inputs());
+        mgr.restore(&state);
+        assert_eq!(expected.inputs(), mgr.inputs());
+        mgr.update(Inputs(&[0, 1]));
+        assert!(mgr.handle(0));
+        assert!(mgr.handle(1));
+
+        // Restored by input into another manager.
+        let mut other = InputManager::<u8, u8>::default();
+        other.register(1, InputCounter::one_shot());
+        other.register(0, InputCounter::auto_repeat(3, 2));
+        other.restore(&state);
+        assert_eq!(expected.inputs(), other.inputs());
+
+        // The buffer is reused.
+        let mut state = InputManagerState::default();
+        mgr.snapshot_into(&mut state);
+        let ptr = state.counters.as_ptr();
+        mgr.update(Inputs(&[]));
+        mgr.snapshot_into(&mut state);
+        assert_eq!(ptr, state.counters.as_ptr());
+        assert_eq!(mgr.snapshot(), state);
+    }
+
+    #[test]
+    #[should_panic]
+    fn restore_different_inputs() {
+        let mut mgr = InputManager::<u8, u8>::default();
+        mgr.register(0, InputCounter::one_shot());
+        let state = mgr.snapshot();
+        let mut other = InputManager::<u8, u8>::default();
+        other.register(1, InputCounter::one_shot());
+        other.restore(&state);
+    }
+
+    #[test]
+    fn fractional_repeat() {
+        let mut c = InputCounter::new(0.5, 3.0);
+        c.update(true);
+        assert!(c.handle());
+        assert!(!c.handle());
+        c.update(true);
+        c.update(true);
+        assert!(!c.handle());
+        c.update(true);
+        // DAS charged
+        assert!(c.handle());
+        assert!(!c.handle());
+        for _ in 0..3 {
+            c.update(true);
+            assert!(c.handle());
+            assert!(c.handle());
+            assert!(!c.handle());
+        }
+
+        let mut c = InputCounter::new(1.5, 2.5);
+        c.update(true);
+        assert!(c.handle());
+        let mut handled = [0; 8];
+        for n in handled.iter_mut() {
+            c.update(true);
+            while c.handle() {
+                *n += 1;
+            }
+        }
+        // DAS at 2.5 frames, then every 1.5 frames.
+        assert_eq!([0, 0, 1, 1, 0, 1, 1, 0], handled);
+    }
+
+    #[test]
+    fn update_by() {
+        for n in 0..10 {
+            for frames in 0..10 {
+                let mut c1 = InputCounter::new(2, 3);
+                for _ in 0..n {
+                    c1.update(true);
+                    if n % 3 == 0 {
+                        c1.handle();
+                    }
+                }
+                let mut c2 = c1;
+                for _ in 0..frames {
+                    c1.update(true);
+                }
+                c2.update_by(true, frames);
+                assert_eq!(c1, c2, "n={}, frames={}", n, frames);
+                c1.handle();
+                c2.handle();
+                c1.update(true);
+                c2.update_by(true, 1);
+                assert_eq!(c1, c2, "n={}, frames={}", n, frames);
+            }
+        }
+
+        let mut c1 = InputCounter::new(2, 3);
+        let mut c2 = c1;
+        for _ in 0..3 {
+            c1.update(true);
+        }
+        c2.update_by(true, 3);
+        assert_eq!(c1, c2);
+        c2.update_by(false, 5);
+        assert!(!c2.can_handle());
+        assert_eq!(InputCounter::new(2, 3), c2);
+    }
+
+    #[test]
+    fn update_by_fractional() {
+        for &(das, arr) in &[(2.5, 0.5), (2.5, 1.5), (1.0, 0.25)] {
+            for n in 0..10 {
+                for frames in 0..10 {
+                    let mut c1 = InputCounter::<f64>::auto_repeat(das, arr);
+                    for _ in 0..n {
+                        c1.update(true);
+                        c1.handle();
+                    }
+                    let mut c2 = c1;
+                    for _ in 0..frames {
+                        c1.update(true);
+                    }
+                    c2.update_by(true, frames as f64);
+                    assert_eq!(
+                        c1, c2,
+                        "das={}, arr={}, n={}, frames={}",
+                        das, arr, n, frames
+                    );
+                }
+            }
+        }
+
+        let mut c = InputCounter::<f64>::auto_repeat(1.0, 0.5);
+        c.update(true);
+        assert!(c.handle());
+        c.update_by(true, 1.0);
+        assert!(c.handle());
+        c.update_by(true, 1.0);
+        assert!(c.handle());
+        assert!(c.handle());
+        assert!(!c.handle());
+    }
 }
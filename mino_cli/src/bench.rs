@@ -0,0 +1,157 @@
+use super::helper;
+use mino_core::common::{
+    Game, GameConfig, GameData, GameEvent, GameStateId, Input, Playfield, SearchGame,
+};
+use mino_core::tetro::{Piece, PieceGrid, WorldRuleLogic};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::error::Error;
+use std::time;
+
+#[derive(Debug, Copy, Clone, Default)]
+struct BenchResult {
+    frames: u64,
+    lines: usize,
+    game_overs: usize,
+    finishes: usize,
+}
+
+/// `seed` is recorded to the game if `rng` is newly seeded with it.
+fn new_game(rng: &mut StdRng, seed: Option<u64>) -> Game<Piece, WorldRuleLogic> {
+    let config = GameConfig::sprint();
+    let mut data = GameData::new(
+        Playfield {
+            visible_rows: 20,
+            grid: PieceGrid::new(10, 40, vec![]),
+        },
+        None,
+        None,
+        helper::generate_pieces_with_rng(rng),
+        &config.params,
+    );
+    data.input_manager = mino_core::common::create_input_manager_for_automation();
+    data.seed = seed;
+    Game::new(config, data)
+}
+
+/// Drive games by a bot which moves each piece to a random column and hard drops it.
+fn simulate(frames: u64, seed: u64) -> Result<BenchResult, Box<dyn Error>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut game = new_game(&mut rng, Some(seed));
+    let mut result = BenchResult::default();
+    let mut target_x = 0;
+    let mut last_x = None;
+    for _ in 0..frames {
+        if game.data().next_pieces.len() <= Piece::num() {
+            let mut ps = helper::generate_pieces_with_rng(&mut rng);
+            game.append_next_pieces(&mut ps);
+        }
+        let input = match (game.state_id(), game.data().falling_piece) {
+            (GameStateId::Play, Some(fp)) => {
+                if last_x == Some(fp.x) || fp.x == target_x {
+                    // reached the target or blocked
+                    last_x = None;
+                    Input::HARD_DROP
+                } else {
+                    last_x = Some(fp.x);
+                    if fp.x < target_x {
+                        Input::MOVE_RIGHT
+                    } else {
+                        Input::MOVE_LEFT
+                    }
+                }
+            }
+            _ => Input::default(),
+        };
+        game.update(input);
+        result.frames += 1;
+        for event in &game.data().events {
+            match event {
+                GameEvent::LineCleared(n, _) => result.lines += n,
+                GameEvent::EnterState(GameStateId::Play) => target_x = rng.gen_range(-1, 9),
+                _ => {}
+            }
+        }
+        match game.state_id() {
+            GameStateId::GameOver => {
+                result.game_overs += 1;
+                game = new_game(&mut rng, None);
+            }
+            GameStateId::Finished => {
+                result.finishes += 1;
+                game = new_game(&mut rng, None);
+            }
+            GameStateId::Error => return Err(format!("{:?}", game).into()),
+            _ => {}
+        }
+    }
+    Ok(result)
+}
+
+/// Compare cloning the whole game data with `fork_for_search()`.
+/// `Game` itself is not `Clone` because of the boxed state, so `GameData` is cloned instead.
+/// Both hard drop with `SearchGame` so that only the copies differ.
+fn compare_fork(iterations: u64, seed: u64) -> (time::Duration, time::Duration) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut game = new_game(&mut rng, Some(seed));
+    game.update_until(GameStateId::Play, 1000);
+
+    let started_at = time::Instant::now();
+    for _ in 0..iterations {
+        let data = game.data().clone();
+        let mut g = SearchGame {
+            playfield: data.playfield,
+            falling_piece: data.falling_piece,
+            hold_piece: data.hold_piece,
+            next_pieces: data.next_pieces,
+            num_holds: data.num_holds_this_piece,
+            params: game.config().params,
+            logic: &game.config().logic,
+        };
+        g.hard_drop();
+    }
+    let clone_elapsed = started_at.elapsed();
+
+    let started_at = time::Instant::now();
+    for _ in 0..iterations {
+        let mut g = game.fork_for_search();
+        g.hard_drop();
+    }
+    let fork_elapsed = started_at.elapsed();
+    (clone_elapsed, fork_elapsed)
+}
+
+pub fn run(frames: u64, seed: u64) -> Result<(), Box<dyn Error>> {
+    let started_at = time::Instant::now();
+    let result = simulate(frames, seed)?;
+    let elapsed = started_at.elapsed().as_secs_f64();
+    println!("frames: {}", result.frames);
+    println!("elapsed: {:.3}s", elapsed);
+    println!("frames/sec: {:.1}", result.frames as f64 / elapsed);
+    println!("lines: {}", result.lines);
+    println!("game overs: {}", result.game_overs);
+    println!("finished sprints: {}", result.finishes);
+    let (clone_elapsed, fork_elapsed) = compare_fork(frames, seed);
+    println!(
+        "clone data vs fork_for_search ({} times): {:.3}s vs {:.3}s",
+        frames,
+        clone_elapsed.as_secs_f64(),
+        fork_elapsed.as_secs_f64()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simulate_smoke() {
+        let result = simulate(500, 1).unwrap();
+        assert_eq!(500, result.frames);
+        let again = simulate(500, 1).unwrap();
+        assert_eq!(result.lines, again.lines);
+        assert_eq!(result.game_overs, again.game_overs);
+        assert_eq!(result.finishes, again.finishes);
+    }
+}
@@ -1,14 +1,17 @@
-use mino_core::common::{Game, GameStateId, Input};
-use mino_core::tetro::{Piece, WorldRuleLogic};
+use mino_core::tetro::Piece;
 use rand::seq::SliceRandom;
+use rand::Rng;
 use std::collections::VecDeque;
 
 pub mod full_screen;
 
 pub fn generate_pieces() -> VecDeque<Piece> {
-    let mut rng = rand::thread_rng();
-    let mut ps = Piece::slice().clone();
-    ps.shuffle(&mut rng);
+    generate_pieces_with_rng(&mut rand::thread_rng())
+}
+
+pub fn generate_pieces_with_rng<R: Rng>(rng: &mut R) -> VecDeque<Piece> {
+    let mut ps = *Piece::slice();
+    ps.shuffle(rng);
     ps.to_vec().into()
 }
 
@@ -21,20 +24,3 @@ pub fn tspin_num_to_en_str_long(n: u8) -> &'static str {
         _ => "",
     }
 }
-
-pub fn update_util(
-    game: &mut Game<Piece, WorldRuleLogic>,
-    state_id: GameStateId,
-    limit: i32,
-) -> bool {
-    for i in 0.. {
-        if game.state_id() == state_id {
-            return true;
-        }
-        game.update(Input::default());
-        if limit > 0 && i > limit {
-            return false;
-        }
-    }
-    false
-}
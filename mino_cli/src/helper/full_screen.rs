@@ -1,8 +1,8 @@
-use grid::IsEmpty;
-use mino_core::common::{Cell, FallingPiece, GameData, TSpin};
+use mino_core::common::{Cell, GameData, GameParams, TSpin};
 use mino_core::tetro::Piece;
 use std::io;
 use std::io::Read;
+use std::str::FromStr;
 use termion::raw::{IntoRawMode, RawTerminal};
 use tui::backend::TermionBackend;
 use tui::layout::Rect;
@@ -25,68 +25,120 @@ pub fn init_terminal() -> Result<
     Ok((terminal, stdin))
 }
 
-struct ViewDataBuilder {
-    ghost_piece: Option<FallingPiece<Piece>>,
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Theme {
+    #[default]
+    Guideline,
+    Monochrome,
 }
 
-impl ViewDataBuilder {
-    fn new(data: &GameData<Piece>) -> Self {
-        Self {
-            ghost_piece: if let Some(fp) = data.falling_piece {
-                let n = fp.droppable_rows(&data.playfield);
-                let mut gp = fp.clone();
-                gp.y -= n as i32;
-                Some(gp)
-            } else {
-                None
+impl Theme {
+    pub fn cell_color(&self, cell: Cell<Piece>) -> Color {
+        match self {
+            Theme::Guideline => match cell {
+                Cell::Block(p) => match p {
+                    Piece::I => Color::Cyan,
+                    Piece::O => Color::Yellow,
+                    Piece::T => Color::Magenta,
+                    Piece::J => Color::Blue,
+                    Piece::L => Color::LightRed,
+                    Piece::S => Color::Green,
+                    Piece::Z => Color::Red,
+                },
+                Cell::Ghost(_) => Color::DarkGray,
+                Cell::Garbage => Color::Gray,
+                _ => Color::Black,
+            },
+            Theme::Monochrome => match cell {
+                Cell::Block(_) => Color::White,
+                Cell::Ghost(_) => Color::DarkGray,
+                Cell::Garbage => Color::Gray,
+                _ => Color::Black,
             },
         }
     }
+}
 
-    fn get_cell(&self, data: &GameData<Piece>, x: usize, y: usize) -> Cell<Piece> {
-        let pf = &data.playfield;
-        if let Some(fp) = data.falling_piece {
-            let x = x as i32 - fp.x;
-            let y = y as i32 - fp.y;
-            if fp.grid().is_valid_cell_index(x as usize, y as usize) {
-                let c = fp.grid().cell(x as usize, y as usize);
-                if !c.is_empty() {
-                    return c;
-                }
-            }
-        }
-        if let Some(gp) = self.ghost_piece {
-            let x = x as i32 - gp.x;
-            let y = y as i32 - gp.y;
-            if gp.grid().is_valid_cell_index(x as usize, y as usize) {
-                if let Cell::Block(p) = gp.grid().cell(x as usize, y as usize) {
-                    return Cell::Ghost(p);
-                }
-            }
+impl FromStr for Theme {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "guideline" => Ok(Theme::Guideline),
+            "monochrome" => Ok(Theme::Monochrome),
+            _ => Err(format!("unknown theme: {}", s)),
         }
-        pf.grid.cell(x, y)
     }
 }
 
-fn format_cell(cell: Cell<Piece>) -> (String, Color) {
-    match cell {
-        Cell::Block(p) => (
-            format!("{}", p),
-            match p {
-                Piece::I => Color::Cyan,
-                Piece::O => Color::Yellow,
-                Piece::T => Color::Magenta,
-                Piece::J => Color::Blue,
-                Piece::L => Color::LightRed,
-                Piece::S => Color::Green,
-                Piece::Z => Color::Red,
-            },
-        ),
-        Cell::Ghost(p) => (format!("{}", p), Color::DarkGray),
-        _ => (" ".into(), Color::Black),
+/// Per piece overrides of the character and the color, e.g. for custom skins.
+/// Pieces without overrides are rendered by the theme.
+#[derive(Debug, Clone, Default)]
+pub struct PieceAppearance {
+    overrides: [Option<(char, Color)>; 7],
+}
+
+impl PieceAppearance {
+    pub fn set(&mut self, piece: Piece, c: char, color: Color) -> &mut Self {
+        self.overrides[piece as usize] = Some((c, color));
+        self
+    }
+    pub fn get(&self, piece: Piece) -> Option<(char, Color)> {
+        self.overrides[piece as usize]
+    }
+}
+
+fn parse_color(s: &str) -> Result<Color, String> {
+    match s {
+        "black" => Ok(Color::Black),
+        "red" => Ok(Color::Red),
+        "green" => Ok(Color::Green),
+        "yellow" => Ok(Color::Yellow),
+        "blue" => Ok(Color::Blue),
+        "magenta" => Ok(Color::Magenta),
+        "cyan" => Ok(Color::Cyan),
+        "gray" => Ok(Color::Gray),
+        "white" => Ok(Color::White),
+        _ => Err(format!("unknown color: {}", s)),
     }
 }
 
+/// Parse comma separated overrides like "T=△:magenta,I=|:cyan".
+impl FromStr for PieceAppearance {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut appearance = PieceAppearance::default();
+        for item in s.split(',').filter(|item| !item.is_empty()) {
+            let invalid = || format!("invalid piece appearance: {}", item);
+            let (piece, rest) = item.split_once('=').ok_or_else(invalid)?;
+            let (c, color) = rest.split_once(':').ok_or_else(invalid)?;
+            let piece = piece.parse::<Piece>().map_err(|e| e.to_string())?;
+            let mut chars = c.chars();
+            let c = match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => return Err(invalid()),
+            };
+            appearance.set(piece, c, parse_color(color)?);
+        }
+        Ok(appearance)
+    }
+}
+
+fn format_cell(cell: Cell<Piece>, theme: &Theme, appearance: &PieceAppearance) -> (String, Color) {
+    let s = match cell.piece() {
+        Some(p) => match appearance.get(p) {
+            Some((c, _)) => c.to_string(),
+            None => format!("{}", p),
+        },
+        None if cell.is_garbage() => format!("{}", cell),
+        None => " ".into(),
+    };
+    let color = match cell {
+        Cell::Block(p) => appearance.get(p).map(|(_, color)| color),
+        _ => None,
+    };
+    (s, color.unwrap_or_else(|| theme.cell_color(cell)))
+}
+
 #[derive(Default, Copy, Clone)]
 pub struct LineClearInfo {
     pub n: usize,
@@ -96,8 +148,11 @@ pub struct LineClearInfo {
 pub fn render<B>(
     f: &mut tui::Frame<B>,
     data: &GameData<Piece>,
+    params: &GameParams,
     line_clear_info: Option<LineClearInfo>,
     pos: (u16, u16),
+    theme: &Theme,
+    appearance: &PieceAppearance,
 ) where
     B: tui::backend::Backend,
 {
@@ -105,7 +160,7 @@ pub fn render<B>(
     {
         let mut text = vec![Text::raw("HOLD:")];
         let t = if let Some(p) = data.hold_piece {
-            format_cell(Cell::Block(p))
+            format_cell(Cell::Block(p), theme, appearance)
         } else {
             ("     ".into(), Color::Black)
         };
@@ -118,7 +173,7 @@ pub fn render<B>(
         let mut ts: Vec<(String, Color)> = Vec::new();
         for i in 0..5 {
             let t = if let Some(p) = data.next_pieces.get(i) {
-                format_cell(Cell::Block(*p))
+                format_cell(Cell::Block(*p), theme, appearance)
             } else {
                 ("     ".into(), Color::Black)
             };
@@ -132,10 +187,9 @@ pub fn render<B>(
     }
     {
         let pf = &data.playfield;
-        let vdb = ViewDataBuilder::new(&data);
         for y in 0..pf.visible_rows {
             for x in 0..pf.grid.num_cols() {
-                let t = format_cell(vdb.get_cell(&data, x, y));
+                let t = format_cell(data.display_cell(x, y, params), theme, appearance);
                 let text = [Text::styled(t.0, Style::default().fg(Color::Black).bg(t.1))];
                 Paragraph::new(text.iter()).render(
                     f,
@@ -170,3 +224,61 @@ pub fn render<B>(
         Paragraph::new(text.iter()).render(f, Rect::new(0, top, 10, 1));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_garbage_cell() {
+        let theme = Theme::default();
+        let appearance = PieceAppearance::default();
+        let (s, color) = format_cell(Cell::Garbage, &theme, &appearance);
+        assert_eq!("x", s);
+        assert_ne!(Color::Black, color);
+        assert_ne!(format_cell(Cell::Empty, &theme, &appearance).1, color);
+    }
+
+    #[test]
+    fn monochrome_theme() {
+        let theme = Theme::Monochrome;
+        let color = theme.cell_color(Cell::Block(Piece::I));
+        for p in Piece::slice() {
+            assert_eq!(color, theme.cell_color(Cell::Block(*p)));
+        }
+        assert_ne!(theme.cell_color(Cell::Empty), color);
+        assert_eq!(Ok(Theme::Monochrome), "monochrome".parse());
+    }
+
+    #[test]
+    fn piece_appearance() {
+        let theme = Theme::default();
+        let mut appearance = PieceAppearance::default();
+        assert_eq!(
+            ("T".to_string(), theme.cell_color(Cell::Block(Piece::T))),
+            format_cell(Cell::Block(Piece::T), &theme, &appearance)
+        );
+        appearance.set(Piece::T, '△', Color::LightMagenta);
+        assert_eq!(
+            ("△".to_string(), Color::LightMagenta),
+            format_cell(Cell::Block(Piece::T), &theme, &appearance)
+        );
+        // Ghosts keep the theme color.
+        assert_eq!(
+            ("△".to_string(), theme.cell_color(Cell::Ghost(Piece::T))),
+            format_cell(Cell::Ghost(Piece::T), &theme, &appearance)
+        );
+        assert_eq!(
+            ("I".to_string(), theme.cell_color(Cell::Block(Piece::I))),
+            format_cell(Cell::Block(Piece::I), &theme, &appearance)
+        );
+
+        let appearance: PieceAppearance = "T=△:magenta,I=|:cyan".parse().unwrap();
+        assert_eq!(Some(('△', Color::Magenta)), appearance.get(Piece::T));
+        assert_eq!(Some(('|', Color::Cyan)), appearance.get(Piece::I));
+        assert_eq!(None, appearance.get(Piece::O));
+        assert!("T=ab:magenta".parse::<PieceAppearance>().is_err());
+        assert!("T=a".parse::<PieceAppearance>().is_err());
+        assert!("T=a:pink".parse::<PieceAppearance>().is_err());
+    }
+}
@@ -83,7 +83,8 @@ mod cmd {
 
 use super::helper;
 use mino_core::common::{
-    Cell, Game, GameConfig, GameData, GameEvent, GameParams, GameStateId, Input, Playfield, TSpin,
+    Cell, Game, GameConfig, GameData, GameEvent, GameParams, GameRng, GameStateId, Input,
+    Playfield, TSpin,
 };
 use mino_core::tetro::{Piece, PieceGrid, WorldRuleLogic};
 use rustyline::error::ReadlineError;
@@ -93,7 +94,7 @@ use std::error::Error;
 use std::io;
 use termion::color;
 
-fn format_game_data(data: &GameData<Piece>) -> String {
+fn format_game_data(data: &GameData<Piece>, params: &GameParams) -> String {
     let mut s = String::with_capacity(1024);
     //---
     s.push_str("Hold: ");
@@ -115,29 +116,21 @@ fn format_game_data(data: &GameData<Piece>) -> String {
     s.push_str("--+----------\n");
     //---
     let pf = &data.playfield;
-    let fp = &data.falling_piece;
     for py in (0..pf.visible_rows).rev() {
         s.push_str(&format!("{:>02}|", py + 1));
         for px in 0..pf.grid.num_cols() {
-            if let Some(fp) = fp {
-                let x = px as i32 - fp.x;
-                let y = py as i32 - fp.y;
-                if x >= 0 && y >= 0 && fp.grid().is_valid_cell_index(x as usize, y as usize) {
-                    if let Cell::Block(p) = fp.grid().cell(x as usize, y as usize) {
-                        s.push_str(&format!(
-                            "{}{}{}",
-                            color::Fg(color::Yellow),
-                            p,
-                            color::Fg(color::Reset)
-                        ));
-                        continue;
-                    }
+            match data.display_cell(px, py, params) {
+                Cell::Block(p) if matches!(pf.grid.cell(px, py), Cell::Empty) => {
+                    s.push_str(&format!(
+                        "{}{}{}",
+                        color::Fg(color::Yellow),
+                        p,
+                        color::Fg(color::Reset)
+                    ))
                 }
-            }
-            if let Cell::Block(p) = pf.grid.cell(px, py) {
-                s.push_str(&format!("{}", p));
-            } else {
-                s.push(' ');
+                Cell::Block(p) => s.push_str(&format!("{}", p)),
+                c if c.is_garbage() => s.push_str(&format!("{}", c)),
+                _ => s.push(' '),
             }
         }
         s.push('\n');
@@ -177,8 +170,43 @@ fn format_game_data(data: &GameData<Piece>) -> String {
     s
 }
 
+/// Same as `format_game_data` but without colors and line numbers, e.g. for snapshot tests.
+fn format_game_data_ascii(data: &GameData<Piece>, params: &GameParams) -> String {
+    let mut s = String::with_capacity(1024);
+    s.push_str("Hold: ");
+    if let Some(p) = data.hold_piece {
+        s.push_str(&format!("{}", p));
+    }
+    s.push('\n');
+    s.push_str("Next: ");
+    for p in data.next_pieces.iter().take(5) {
+        s.push_str(&format!("{}", p));
+    }
+    s.push('\n');
+    let pf = &data.playfield;
+    let border = format!("+{}+\n", "-".repeat(pf.grid.num_cols()));
+    s.push_str(&border);
+    for py in (0..pf.visible_rows).rev() {
+        s.push('|');
+        for px in 0..pf.grid.num_cols() {
+            match data.display_cell(px, py, params) {
+                Cell::Block(p) => s.push_str(&format!("{}", p)),
+                c if c.is_garbage() => s.push_str(&format!("{}", c)),
+                _ => s.push(' '),
+            }
+        }
+        s.push_str("|\n");
+    }
+    s.push_str(&border);
+    s
+}
+
 trait Renderer {
-    fn render_game_data(&mut self, data: &GameData<Piece>) -> Result<(), Box<dyn Error>>;
+    fn render_game_data(
+        &mut self,
+        data: &GameData<Piece>,
+        params: &GameParams,
+    ) -> Result<(), Box<dyn Error>>;
     fn render_error(&mut self, err: &dyn Error) -> Result<(), Box<dyn Error>>;
     fn render_error_str(&mut self, err: &str) -> Result<(), Box<dyn Error>> {
         let err: Box<dyn Error> = err.into();
@@ -192,8 +220,36 @@ struct HumanReadableRenderer<W: io::Write> {
 }
 
 impl<W: io::Write> Renderer for HumanReadableRenderer<W> {
-    fn render_game_data(&mut self, data: &GameData<Piece>) -> Result<(), Box<dyn Error>> {
-        write!(self.w, "{}", format_game_data(data))?;
+    fn render_game_data(
+        &mut self,
+        data: &GameData<Piece>,
+        params: &GameParams,
+    ) -> Result<(), Box<dyn Error>> {
+        write!(self.w, "{}", format_game_data(data, params))?;
+        Ok(())
+    }
+    fn render_error(&mut self, err: &dyn Error) -> Result<(), Box<dyn Error>> {
+        write!(self.w, "ERROR: {}", err)?;
+        Ok(())
+    }
+    fn render_message(&mut self, msg: &str) -> Result<(), Box<dyn Error>> {
+        write!(self.w, "{}", msg)?;
+        Ok(())
+    }
+}
+
+/// Color-free renderer.
+struct AsciiRenderer<W: io::Write> {
+    w: W,
+}
+
+impl<W: io::Write> Renderer for AsciiRenderer<W> {
+    fn render_game_data(
+        &mut self,
+        data: &GameData<Piece>,
+        params: &GameParams,
+    ) -> Result<(), Box<dyn Error>> {
+        write!(self.w, "{}", format_game_data_ascii(data, params))?;
         Ok(())
     }
     fn render_error(&mut self, err: &dyn Error) -> Result<(), Box<dyn Error>> {
@@ -207,35 +263,42 @@ impl<W: io::Write> Renderer for HumanReadableRenderer<W> {
 }
 
 fn new_game() -> Game<Piece, WorldRuleLogic> {
+    new_game_with(
+        Playfield {
+            visible_rows: 20,
+            grid: PieceGrid::new(10, 40, vec![]),
+        },
+        helper::generate_pieces(),
+    )
+}
+
+fn new_game_with(
+    playfield: Playfield<Piece>,
+    next_pieces: VecDeque<Piece>,
+) -> Game<Piece, WorldRuleLogic> {
+    // Pieces move only by commands.
+    let sprint = GameConfig::sprint();
     let config = GameConfig {
         params: GameParams {
             gravity: 0.0,
-            are: 0,
             lock_delay: 60 * 60 * 60 * 24,
-            line_clear_delay: 0,
-            ..GameParams::default()
+            line_goal: None,
+            ..sprint.params
         },
-        logic: WorldRuleLogic::default(),
+        ..sprint
     };
-    let mut data = GameData::new(
-        Playfield {
-            visible_rows: 20,
-            grid: PieceGrid::new(10, 40, vec![]),
-        },
-        None,
-        None,
-        helper::generate_pieces(),
-        &config.params,
-    );
+    let mut data = GameData::new(playfield, None, None, next_pieces, &config.params);
     data.input_manager = mino_core::common::create_input_manager_for_automation();
     let mut game = Game::new(config, data);
-    helper::update_util(&mut game, GameStateId::Play, 1000);
+    game.update_until(GameStateId::Play, 1000);
     game
 }
 
 #[derive(Debug, Clone, Copy, Default)]
 struct Opts {
     autogen: bool,
+    /// Random if None.
+    cheese_seed: Option<u64>,
 }
 
 struct App {
@@ -250,6 +313,8 @@ const HELP: &'static str = r#"Commands:
 - print|p
 - move|mv <MOVE>=<N> ...
 - set autogen=<BOOL>
+- set cheese_seed=<N>
+- set cheese=<N>
 - next [add=<PIECES>] [set=<PIECES>] [auto(=force)]
 - history
 "#;
@@ -290,7 +355,7 @@ impl App {
                 self.game = new_game();
             }
             "print" | "p" => {
-                renderer.render_game_data(self.game.data())?;
+                renderer.render_game_data(self.game.data(), &self.game.config().params)?;
             }
             "move" | "mv" => {
                 for arg in args {
@@ -344,7 +409,7 @@ impl App {
                         }
                     }
                 }
-                renderer.render_game_data(self.game.data())?;
+                renderer.render_game_data(self.game.data(), &self.game.config().params)?;
             }
             "set" => {
                 for arg in args {
@@ -364,6 +429,42 @@ impl App {
                                 return Ok(true);
                             }
                         }
+                        "cheese_seed" => {
+                            if let Some(v) = arg.value {
+                                self.opts.cheese_seed = match v.parse::<u64>() {
+                                    Ok(v) => Some(v),
+                                    Err(err) => {
+                                        renderer.render_error(&err)?;
+                                        return Ok(true);
+                                    }
+                                };
+                            } else {
+                                renderer.render_error_str("value is required\n")?;
+                                return Ok(true);
+                            }
+                        }
+                        "cheese" => {
+                            if let Some(v) = arg.value {
+                                let lines = match v.parse::<usize>() {
+                                    Ok(v) if v < 20 => v,
+                                    Ok(_) => {
+                                        renderer.render_error_str("too many lines\n")?;
+                                        return Ok(true);
+                                    }
+                                    Err(err) => {
+                                        renderer.render_error(&err)?;
+                                        return Ok(true);
+                                    }
+                                };
+                                if let Err(err) = self.set_cheese(lines) {
+                                    renderer.render_error_str(&format!("{}\n", err))?;
+                                    return Ok(true);
+                                }
+                            } else {
+                                renderer.render_error_str("value is required\n")?;
+                                return Ok(true);
+                            }
+                        }
                         _ => {
                             renderer.render_error_str(&format!("unknown option: {}\n", arg.key))?;
                             return Ok(true);
@@ -412,7 +513,7 @@ impl App {
 
     fn input(&mut self, input: Input) {
         self.game.update(input);
-        helper::update_util(&mut self.game, GameStateId::Play, 1000);
+        self.game.update_until(GameStateId::Play, 1000);
         self.gen(false);
     }
 
@@ -422,21 +523,41 @@ impl App {
         }
     }
 
+    /// Restart the game with `lines` cheese garbage rows generated by `opts.cheese_seed`.
+    fn set_cheese(&mut self, lines: usize) -> Result<(), String> {
+        let mut playfield = Playfield {
+            visible_rows: 20,
+            grid: PieceGrid::new(10, 40, vec![]),
+        };
+        let seed = self.opts.cheese_seed.unwrap_or_else(rand::random);
+        playfield.fill_cheese(lines, &mut GameRng::new(seed))?;
+        self.game = new_game_with(playfield, helper::generate_pieces());
+        self.gen(false);
+        Ok(())
+    }
+
     fn generate_next_pieces(&mut self) {
         let mut ps = helper::generate_pieces();
         self.game.append_next_pieces(&mut ps);
     }
 }
 
-pub fn run() -> Result<(), Box<dyn Error>> {
+pub fn run(ascii: bool) -> Result<(), Box<dyn Error>> {
+    if ascii {
+        run_with_renderer(&mut AsciiRenderer { w: io::stdout() })
+    } else {
+        run_with_renderer(&mut HumanReadableRenderer { w: io::stdout() })
+    }
+}
+
+fn run_with_renderer<R: Renderer>(renderer: &mut R) -> Result<(), Box<dyn Error>> {
     let mut app = App::new();
-    let mut renderer = HumanReadableRenderer { w: io::stdout() };
     let mut rl = Editor::<()>::new();
     loop {
         let readline = rl.readline("> ");
         match readline {
             Ok(line) => {
-                if !app.parse_line(&line, &mut renderer)? {
+                if !app.parse_line(&line, renderer)? {
                     break;
                 }
             }
@@ -455,3 +576,73 @@ pub fn run() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_renderer() {
+        let playfield = Playfield {
+            visible_rows: 20,
+            grid: PieceGrid::new(10, 40, vec![]),
+        };
+        let next = vec![
+            Piece::T,
+            Piece::O,
+            Piece::I,
+            Piece::S,
+            Piece::Z,
+            Piece::J,
+            Piece::L,
+        ];
+        let game = new_game_with(playfield, next.into());
+        let mut renderer = AsciiRenderer { w: Vec::new() };
+        renderer
+            .render_game_data(game.data(), &game.config().params)
+            .unwrap();
+        // No hold piece yet.
+        let mut expected = String::from("Hold: \nNext: OISZJ\n+----------+\n");
+        expected.push_str("|   TTT    |\n");
+        expected.push_str(&"|          |\n".repeat(19));
+        expected.push_str("+----------+\n");
+        assert_eq!(expected, String::from_utf8(renderer.w).unwrap());
+    }
+
+    #[test]
+    fn set_cheese() {
+        let mut app = App::new();
+        let mut renderer = AsciiRenderer { w: Vec::new() };
+        assert!(app.parse_line("set cheese=8", &mut renderer).unwrap());
+        assert!(renderer.w.is_empty());
+        let grid = &app.game.data().playfield.grid;
+        let holes: Vec<_> = (0..8)
+            .map(|y| {
+                let row = grid.row_mask(y);
+                assert_eq!(1, row.iter().filter(|filled| !**filled).count());
+                assert!((0..10).all(|x| matches!(grid.cell(x, y), Cell::Garbage | Cell::Empty)));
+                row.iter().position(|filled| !filled).unwrap()
+            })
+            .collect();
+        assert!(holes.windows(2).all(|w| w[0] != w[1]));
+        assert_eq!(0, grid.count_filled_in(0..10, 8..20));
+        assert_eq!(GameStateId::Play, app.game.state_id());
+
+        assert!(app.parse_line("set cheese=20", &mut renderer).unwrap());
+        assert!(!renderer.w.is_empty());
+
+        let mut renderer = AsciiRenderer { w: Vec::new() };
+        assert!(app
+            .parse_line("set cheese_seed=3 cheese=8", &mut renderer)
+            .unwrap());
+        let grid = app.game.data().playfield.grid.clone();
+        assert!(app.parse_line("set cheese=8", &mut renderer).unwrap());
+        assert!(renderer.w.is_empty());
+        assert!(app
+            .game
+            .data()
+            .playfield
+            .grid
+            .equals_by(&grid, Cell::eq_ignoring_ghost));
+    }
+}
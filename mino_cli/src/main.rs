@@ -5,24 +5,99 @@ extern crate rand;
 extern crate rustyline;
 extern crate termion;
 extern crate tui;
-use clap::{App, SubCommand};
+use clap::{App, Arg, SubCommand};
 
+mod bench;
 mod helper;
 mod interactive;
 mod play;
+mod replay;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let matches = App::new("mino_cli")
-        .subcommand(SubCommand::with_name("play").alias("p"))
-        .subcommand(SubCommand::with_name("interactive").alias("i"))
+        .subcommand(
+            SubCommand::with_name("play")
+                .alias("p")
+                .arg(
+                    Arg::with_name("theme")
+                        .long("theme")
+                        .takes_value(true)
+                        .possible_values(&["guideline", "monochrome"])
+                        .default_value("guideline"),
+                )
+                .arg(
+                    Arg::with_name("piece-appearance")
+                        .long("piece-appearance")
+                        .takes_value(true)
+                        .help("Overrides like \"T=△:magenta,I=|:cyan\"")
+                        .default_value(""),
+                )
+                .arg(
+                    Arg::with_name("record")
+                        .long("record")
+                        .takes_value(true)
+                        .help("Save the inputs to the file for replay"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("interactive")
+                .alias("i")
+                .arg(Arg::with_name("ascii").long("ascii")),
+        )
+        .subcommand(
+            SubCommand::with_name("bench")
+                .alias("b")
+                .arg(
+                    Arg::with_name("frames")
+                        .long("frames")
+                        .takes_value(true)
+                        .default_value("100000"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .takes_value(true)
+                        .default_value("0"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("replay")
+                .alias("r")
+                .arg(Arg::with_name("file").required(true))
+                .arg(
+                    Arg::with_name("speed")
+                        .long("speed")
+                        .takes_value(true)
+                        .default_value("1.0"),
+                ),
+        )
         .get_matches();
 
-    if let Some(_matches) = matches.subcommand_matches("play") {
-        return play::run();
+    if let Some(matches) = matches.subcommand_matches("play") {
+        let theme = matches
+            .value_of("theme")
+            .unwrap()
+            .parse::<helper::full_screen::Theme>()?;
+        let appearance = matches
+            .value_of("piece-appearance")
+            .unwrap()
+            .parse::<helper::full_screen::PieceAppearance>()?;
+        return play::run(theme, appearance, matches.value_of("record"));
     }
 
-    if let Some(_matches) = matches.subcommand_matches("interactive") {
-        return interactive::run();
+    if let Some(matches) = matches.subcommand_matches("interactive") {
+        return interactive::run(matches.is_present("ascii"));
+    }
+
+    if let Some(matches) = matches.subcommand_matches("bench") {
+        let frames = matches.value_of("frames").unwrap().parse::<u64>()?;
+        let seed = matches.value_of("seed").unwrap().parse::<u64>()?;
+        return bench::run(frames, seed);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("replay") {
+        let speed = matches.value_of("speed").unwrap().parse::<f64>()?;
+        return replay::run(matches.value_of("file").unwrap(), speed);
     }
 
     Ok(())
@@ -1,38 +1,56 @@
 use super::helper;
-use mino_core::common::{Game, GameConfig, GameData, GameEvent, GameParams, Input, Playfield};
+use super::replay::Recording;
+use mino_core::common::{Game, GameConfig, GameData, GameEvent, Input, Playfield};
 use mino_core::tetro::{Piece, PieceGrid, WorldRuleLogic};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use std::time;
 use termion::event::{Event, Key};
 use tui::layout::{Constraint, Direction, Layout};
 use tui::style::{Color, Style};
 use tui::widgets::{Block, Paragraph, Text, Widget};
 
-pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// Create the game played by `run`, also used to replay its recordings.
+/// The pieces are generated by the returned rng, cf. `update()`.
+pub fn new_game(seed: u64) -> (Game<Piece, WorldRuleLogic>, StdRng) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let config = GameConfig::marathon();
+    let mut data = GameData::new(
+        Playfield {
+            visible_rows: 20,
+            grid: PieceGrid::new(10, 40, vec![]),
+        },
+        None,
+        None,
+        helper::generate_pieces_with_rng(&mut rng),
+        &config.params,
+    );
+    data.seed = Some(seed);
+    (Game::new(config, data), rng)
+}
+
+/// Update the game by `input`, generating the next pieces with `rng` beforehand if running low.
+pub fn update(game: &mut Game<Piece, WorldRuleLogic>, rng: &mut StdRng, input: Input) {
+    if game.data().next_pieces.len() <= Piece::num() {
+        let mut ps = helper::generate_pieces_with_rng(rng);
+        game.append_next_pieces(&mut ps);
+    }
+    game.update(input);
+}
+
+/// Play a game. The inputs are saved to `record_path` at the end if given, cf. `replay::run()`.
+pub fn run(
+    theme: helper::full_screen::Theme,
+    appearance: helper::full_screen::PieceAppearance,
+    record_path: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
     const FRAME_TIME: time::Duration = time::Duration::from_micros(16666);
 
-    let mut game = {
-        let config = GameConfig {
-            params: GameParams {
-                // gravity: 0.0167,
-                gravity: 0.0,
-                are: 0,
-                lock_delay: 60 * 60 * 60 * 24,
-                line_clear_delay: 0,
-                ..GameParams::default()
-            },
-            logic: WorldRuleLogic::default(),
-        };
-        let data = GameData::new(
-            Playfield {
-                visible_rows: 20,
-                grid: PieceGrid::new(10, 40, vec![]),
-            },
-            None,
-            None,
-            helper::generate_pieces(),
-            &config.params,
-        );
-        Game::new(config, data)
+    let seed = rand::random::<u64>();
+    let (mut game, mut rng) = new_game(seed);
+    let mut recording = Recording {
+        seed,
+        inputs: Vec::new(),
     };
 
     let (mut terminal, mut stdin) = helper::full_screen::init_terminal()?;
@@ -43,11 +61,6 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
     loop {
         let frame_started_at = time::Instant::now();
 
-        if game.data().next_pieces.len() <= Piece::num() {
-            let mut ps = helper::generate_pieces();
-            game.append_next_pieces(&mut ps);
-        }
-
         let mut input = Input::default();
         if let Some(Ok(item)) = stdin.next() {
             if let Ok(ev) = termion::event::parse_event(item, &mut stdin) {
@@ -70,7 +83,8 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
                 break;
             }
         }
-        game.update(input);
+        update(&mut game, &mut rng, input);
+        recording.inputs.push(input);
 
         for event in &game.data().events {
             match event {
@@ -97,6 +111,7 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
             helper::full_screen::render(
                 &mut f,
                 game.data(),
+                &game.config().params,
                 if line_clear.1 > 0 {
                     line_clear.1 -= 1;
                     Some(line_clear.0.clone())
@@ -104,6 +119,8 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
                     None
                 },
                 (0, 0),
+                &theme,
+                &appearance,
             );
             // Right pane
             {
@@ -120,5 +137,9 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
             std::thread::sleep(FRAME_TIME - dt);
         }
     }
+    drop(terminal);
+    if let Some(path) = record_path {
+        std::fs::write(path, recording.to_string())?;
+    }
     Ok(())
 }
@@ -0,0 +1,191 @@
+use super::helper;
+use super::play;
+use mino_core::common::{Game, GameStateId, Input};
+use mino_core::tetro::{Piece, WorldRuleLogic};
+use rand::rngs::StdRng;
+use std::fmt;
+use std::str::FromStr;
+use std::time;
+use termion::event::{Event, Key};
+use tui::layout::{Constraint, Direction, Layout};
+use tui::style::{Color, Style};
+use tui::widgets::{Block, Paragraph, Text, Widget};
+
+/// Inputs of a game created by `play::new_game()` with a seed.
+///
+/// The text format is the header line "seed <seed>" followed by the input of each frame
+/// per line as `Input::to_token()`. An empty line means no input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    pub seed: u64,
+    pub inputs: Vec<Input>,
+}
+
+impl fmt::Display for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "seed {}", self.seed)?;
+        for input in &self.inputs {
+            writeln!(f, "{}", input.to_token())?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Recording {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines();
+        let header = lines.next().unwrap_or("");
+        let seed = match header.split_once(' ') {
+            Some(("seed", seed)) => seed
+                .parse::<u64>()
+                .map_err(|e| format!("invalid seed: {}", e))?,
+            _ => return Err(format!("invalid header: {}", header)),
+        };
+        let inputs = lines
+            .map(|line| Input::from_token(line.trim()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { seed, inputs })
+    }
+}
+
+/// Reconstruct the recorded game and update it frame by frame.
+struct Replayer {
+    game: Game<Piece, WorldRuleLogic>,
+    rng: StdRng,
+    inputs: std::vec::IntoIter<Input>,
+}
+
+impl Replayer {
+    fn new(recording: Recording) -> Self {
+        let (game, rng) = play::new_game(recording.seed);
+        Self {
+            game,
+            rng,
+            inputs: recording.inputs.into_iter(),
+        }
+    }
+
+    /// Update the game by the next input.
+    /// Return false at game over, at the goal or at the end of inputs.
+    fn step(&mut self) -> bool {
+        if let GameStateId::GameOver | GameStateId::Finished = self.game.state_id() {
+            return false;
+        }
+        let input = match self.inputs.next() {
+            Some(input) => input,
+            None => return false,
+        };
+        play::update(&mut self.game, &mut self.rng, input);
+        true
+    }
+}
+
+/// Play back the recording in `path` at `speed` times the real time. Press 'q' to quit.
+pub fn run(path: &str, speed: f64) -> Result<(), Box<dyn std::error::Error>> {
+    if speed <= 0.0 {
+        return Err(format!("invalid speed: {}", speed).into());
+    }
+    let frame_time = time::Duration::from_micros(16666).div_f64(speed);
+    let recording = std::fs::read_to_string(path)?.parse::<Recording>()?;
+    let mut replayer = Replayer::new(recording);
+    let theme = helper::full_screen::Theme::Guideline;
+    let appearance = helper::full_screen::PieceAppearance::default();
+
+    let (mut terminal, mut stdin) = helper::full_screen::init_terminal()?;
+
+    while replayer.step() {
+        let frame_started_at = time::Instant::now();
+
+        if let Some(Ok(item)) = stdin.next() {
+            if let Ok(Event::Key(Key::Char('q'))) = termion::event::parse_event(item, &mut stdin) {
+                break;
+            }
+        }
+
+        let game = &replayer.game;
+        terminal.draw(|mut f| {
+            let size = f.size();
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Length(10), Constraint::Percentage(90)].as_ref())
+                .split(size);
+            Block::default()
+                .style(Style::default().bg(Color::Black))
+                .render(&mut f, size);
+            // Left pane
+            helper::full_screen::render(
+                &mut f,
+                game.data(),
+                &game.config().params,
+                None,
+                (0, 0),
+                &theme,
+                &appearance,
+            );
+            // Right pane
+            {
+                let text = [Text::raw(format!(
+                    "frame: {}\nseed: {:?}",
+                    game.frame_num(),
+                    game.seed()
+                ))];
+                Paragraph::new(text.iter())
+                    .style(Style::default().fg(Color::White).bg(Color::Black))
+                    .wrap(true)
+                    .render(&mut f, chunks[1]);
+            }
+        })?;
+
+        let dt = time::Instant::now() - frame_started_at;
+        if dt < frame_time {
+            std::thread::sleep(frame_time - dt);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mino_core::common::Cell;
+
+    #[test]
+    fn replay_recording() {
+        // Record a game as `play::run()` does.
+        let (mut game, mut rng) = play::new_game(1);
+        let first = game.data().next_pieces[0];
+        let mut recording = Recording {
+            seed: 1,
+            inputs: Vec::new(),
+        };
+        for &input in &[Input::MOVE_LEFT, Input::HARD_DROP, Input::default()] {
+            while game.state_id() != GameStateId::Play {
+                play::update(&mut game, &mut rng, Input::default());
+                recording.inputs.push(Input::default());
+            }
+            play::update(&mut game, &mut rng, input);
+            recording.inputs.push(input);
+        }
+        let recording = recording.to_string().parse::<Recording>().unwrap();
+
+        let mut replayer = Replayer::new(recording);
+        while replayer.step() {}
+        let replayed = &replayer.game;
+        assert_eq!(game.frame_num(), replayed.frame_num());
+        assert_eq!(Some(1), replayed.seed());
+        assert_eq!(
+            game.data().to_board_string(),
+            replayed.data().to_board_string()
+        );
+        let grid = &replayed.data().playfield.grid;
+        assert_eq!(4, grid.count_filled_in(0..10, 0..40));
+        let placed: Vec<_> = grid.iter_filled().collect();
+        assert!(placed
+            .iter()
+            .all(|(_, y, c)| *y < 2 && matches!(c, Cell::Block(p) if *p == first)));
+
+        assert!("seed x\n".parse::<Recording>().is_err());
+        assert!("seed 1\nXX\n".parse::<Recording>().is_err());
+    }
+}
@@ -1,39 +1,19 @@
-use input_counter::{Contains, InputCounter, InputManager};
-use std::collections::VecDeque;
-use std::fmt;
-use std::hash::Hash;
-
-#[derive(Debug, Copy, Clone)]
-pub enum Rotation {
-    Cw0,
-    Cw90,
-    Cw180,
-    Cw270,
-}
-
-impl Rotation {
-    pub fn rotate_cw(&self, n: i8) -> Rotation {
-        match ((*self as i16) + (n as i16) + 4) % 4 {
-            0 => Rotation::Cw0,
-            1 => Rotation::Cw90,
-            2 => Rotation::Cw180,
-            3 => Rotation::Cw270,
-            _ => panic!("never matched"),
-        }
-    }
-    pub fn cw(&self) -> Rotation {
-        self.rotate_cw(1)
-    }
-    pub fn ccw(&self) -> Rotation {
-        self.rotate_cw(-1)
-    }
-}
-
-impl Default for Rotation {
-    fn default() -> Self {
-        Self::Cw0
-    }
-}
+use super::garbage;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use core::error::Error;
+use core::fmt;
+use core::hash::Hash;
+use core::ops::Range;
+use core::str::FromStr;
+use input_counter::{Contains, InputCounter, InputManager, InputState};
+#[cfg(feature = "std")]
+use std::error::Error;
+
+pub use grid::Rotation;
 
 //--- Piece, FallingPiece, Playfield
 
@@ -45,6 +25,12 @@ pub trait Piece: Copy {
     fn grid_bottom_padding(&self, rotation: Rotation) -> usize {
         self.grid(rotation).bottom_padding()
     }
+    fn grid_left_padding(&self, rotation: Rotation) -> usize {
+        self.grid(rotation).left_padding()
+    }
+    fn grid_right_padding(&self, rotation: Rotation) -> usize {
+        self.grid(rotation).right_padding()
+    }
 }
 
 pub type PieceGrid<P> = grid::Grid<Cell<P>>;
@@ -57,6 +43,38 @@ pub enum Cell<P: Piece> {
     Garbage,
 }
 
+impl<P: Piece> Cell<P> {
+    pub fn is_garbage(&self) -> bool {
+        matches!(self, Cell::Garbage)
+    }
+    pub fn is_block(&self) -> bool {
+        matches!(self, Cell::Block(_))
+    }
+    /// Return the piece of `Cell::Block` or `Cell::Ghost`.
+    pub fn piece(&self) -> Option<P> {
+        match self {
+            Cell::Block(p) | Cell::Ghost(p) => Some(*p),
+            Cell::Empty | Cell::Garbage => None,
+        }
+    }
+}
+
+impl<P: Piece + PartialEq> Cell<P> {
+    /// Compare cells treating `Cell::Ghost` as `Cell::Empty`.
+    /// Use with `Grid::equals_by` to compare boards regardless of ghost pieces.
+    pub fn eq_ignoring_ghost(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Cell::Empty, Cell::Empty)
+            | (Cell::Empty, Cell::Ghost(_))
+            | (Cell::Ghost(_), Cell::Empty)
+            | (Cell::Ghost(_), Cell::Ghost(_))
+            | (Cell::Garbage, Cell::Garbage) => true,
+            (Cell::Block(a), Cell::Block(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
 impl<P: Piece> grid::IsEmpty for Cell<P> {
     fn is_empty(&self) -> bool {
         match self {
@@ -82,6 +100,12 @@ impl<P: Piece + fmt::Display> fmt::Display for Cell<P> {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct FallingPiece<P: Piece> {
     pub piece: P,
@@ -100,6 +124,21 @@ impl<P: Piece> FallingPiece<P> {
     pub fn grid_bottom_padding(&self) -> usize {
         self.piece.grid_bottom_padding(self.rotation)
     }
+    pub fn grid_left_padding(&self) -> usize {
+        self.piece.grid_left_padding(self.rotation)
+    }
+    pub fn grid_right_padding(&self) -> usize {
+        self.piece.grid_right_padding(self.rotation)
+    }
+    /// Return the ranges of x and y occupied by the blocks in the playfield coordinates.
+    pub fn bounding_rect(&self) -> (Range<i32>, Range<i32>) {
+        let g = self.grid();
+        let x0 = self.x + self.grid_left_padding() as i32;
+        let x1 = self.x + (g.num_cols() - self.grid_right_padding()) as i32;
+        let y0 = self.y + self.grid_bottom_padding() as i32;
+        let y1 = self.y + (g.num_rows() - self.grid_top_padding()) as i32;
+        (x0..x1, y0..y1)
+    }
     pub fn is_lock_out(&self, playfield: &Playfield<P>) -> bool {
         let padding = self.grid_bottom_padding();
         self.y + padding as i32 >= playfield.visible_rows as i32
@@ -109,10 +148,30 @@ impl<P: Piece> FallingPiece<P> {
         self.y + (self.grid().num_rows() - padding) as i32 >= playfield.visible_rows as i32
     }
     pub fn can_put_onto(&self, playfield: &Playfield<P>) -> bool {
-        playfield
+        !playfield
             .grid
             .check_overlay(self.x, self.y, &self.grid())
-            .is_empty()
+            .blocks_placement()
+    }
+    /// Return the piece moved by (dx, dy) if it can be put onto `playfield`.
+    pub fn try_move(&self, dx: i32, dy: i32, playfield: &Playfield<P>) -> Option<FallingPiece<P>> {
+        let mut fp = *self;
+        fp.x += dx;
+        fp.y += dy;
+        if fp.can_put_onto(playfield) {
+            Some(fp)
+        } else {
+            None
+        }
+    }
+    /// Return the piece rotated by `logic` if it is rotatable.
+    pub fn try_rotate<L: GameLogic<P>>(
+        &self,
+        cw: bool,
+        logic: &L,
+        playfield: &Playfield<P>,
+    ) -> Option<RotationResult<P>> {
+        logic.rotate(cw, self, playfield)
     }
     pub fn put_onto(&self, playfield: &mut Playfield<P>) -> grid::OverlayResult {
         playfield.grid.overlay(self.x, self.y, &self.grid())
@@ -128,6 +187,17 @@ impl<P: Piece> FallingPiece<P> {
             n - 1
         }
     }
+    /// Return how many columns the piece can move toward `side` without collision.
+    pub fn slide_distance(&self, playfield: &Playfield<P>, side: Side) -> usize {
+        let dx = match side {
+            Side::Left => -1,
+            Side::Right => 1,
+        };
+        let (n, _r) = playfield
+            .grid
+            .check_overlay_toward(self.x, self.y, self.grid(), dx, 0);
+        n.saturating_sub(1)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -136,6 +206,159 @@ pub struct Playfield<P: Piece> {
     pub grid: grid::Grid<Cell<P>>,
 }
 
+impl<P: Piece> Playfield<P> {
+    /// Fill all cells with `Cell::Empty`.
+    pub fn clear(&mut self) {
+        let n = self.grid.num_rows();
+        self.grid.fill_rows(0..n, Cell::Empty);
+    }
+    pub fn is_empty(&self) -> bool {
+        self.grid.top_padding() == self.grid.num_rows()
+    }
+    /// Return the grid of the visible rows only.
+    pub fn visible_grid(&self) -> PieceGrid<P> {
+        let num_cols = self.grid.num_cols();
+        self.grid.crop(0..num_cols, 0..self.visible_rows)
+    }
+    /// Return the height of each column.
+    pub fn height_profile(&self) -> Vec<usize> {
+        self.grid.column_heights()
+    }
+    /// Push up all cells by `lines` rows and fill the bottom rows with garbage having a hole
+    /// at a column chosen by `rng`, e.g. for downstacking practice.
+    /// Adjacent rows never have holes in the same column.
+    /// Fail without changes if filled cells would be pushed out of the grid.
+    pub fn fill_cheese(&mut self, lines: usize, rng: &mut GameRng) -> Result<(), String> {
+        let num_cols = self.grid.num_cols();
+        let num_rows = self.grid.num_rows();
+        if num_cols < 2 {
+            return Err("too few columns for cheese".into());
+        }
+        if lines > num_rows
+            || self
+                .grid
+                .count_filled_in(0..num_cols, num_rows - lines..num_rows)
+                > 0
+        {
+            return Err(format!("can not push up filled cells by {} rows", lines));
+        }
+        self.grid.shift(0, lines as i32);
+        let mut prev_hole = None;
+        for y in 0..lines {
+            let hole = match prev_hole {
+                Some(prev) => {
+                    let hole = rng.gen_below(num_cols - 1);
+                    if hole >= prev {
+                        hole + 1
+                    } else {
+                        hole
+                    }
+                }
+                None => rng.gen_below(num_cols),
+            };
+            self.grid.fill_row(y, Cell::Garbage);
+            self.grid.set_cell(hole, y, Cell::Empty);
+            prev_hole = Some(hole);
+        }
+        Ok(())
+    }
+    /// Put `fp` and clear filled rows, then return the number of cleared rows.
+    /// If `fp` can not be put, the playfield is not changed and the overlay result is returned.
+    pub fn lock_piece(&mut self, fp: &FallingPiece<P>) -> Result<usize, grid::OverlayResult> {
+        let r = self.grid.check_overlay(fp.x, fp.y, fp.grid());
+        if !r.is_empty() {
+            return Err(r);
+        }
+        fp.put_onto(self);
+        Ok(self.grid.pluck_filled_rows(Some(Cell::Empty)))
+    }
+}
+
+//--- Compact encoding
+
+/// Pieces identified by indices in `0..num_pieces()`, e.g. for `CompactGrid`.
+pub trait IndexedPiece: Piece {
+    fn num_pieces() -> usize;
+    fn to_index(&self) -> usize;
+    /// Return None if `index` is out of range.
+    fn from_index(index: usize) -> Option<Self>;
+}
+
+/// Pack cells into bytes from bottom-left, e.g. to store or send playfields.
+/// Each cell takes 4 bits for up to 7 pieces, or 8 bits for up to 127 pieces.
+pub trait CompactGrid: Sized {
+    fn to_compact(&self) -> Vec<u8>;
+    /// Unpack bytes generated by `to_compact()`.
+    fn from_compact(cols: usize, rows: usize, bytes: &[u8]) -> Result<Self, Box<dyn Error>>;
+}
+
+fn compact_cell_bits<P: IndexedPiece>() -> usize {
+    // Empty, garbage, and a block and a ghost for each piece.
+    let num_values = 2 + 2 * P::num_pieces();
+    [1, 2, 4, 8]
+        .iter()
+        .copied()
+        .find(|bits| num_values <= 1 << bits)
+        .expect("too many pieces for the compact encoding")
+}
+
+fn encode_cell<P: IndexedPiece>(cell: Cell<P>) -> u8 {
+    let v = match cell {
+        Cell::Empty => 0,
+        Cell::Garbage => 1,
+        Cell::Block(p) => 2 + p.to_index(),
+        Cell::Ghost(p) => 2 + P::num_pieces() + p.to_index(),
+    };
+    v as u8
+}
+
+fn decode_cell<P: IndexedPiece>(v: u8) -> Option<Cell<P>> {
+    let v = v as usize;
+    let n = P::num_pieces();
+    match v {
+        0 => Some(Cell::Empty),
+        1 => Some(Cell::Garbage),
+        _ if v < 2 + n => P::from_index(v - 2).map(Cell::Block),
+        _ => P::from_index(v - 2 - n).map(Cell::Ghost),
+    }
+}
+
+impl<P: IndexedPiece> CompactGrid for PieceGrid<P> {
+    fn to_compact(&self) -> Vec<u8> {
+        let bits = compact_cell_bits::<P>();
+        let cells_per_byte = 8 / bits;
+        let num_cells = self.num_cols() * self.num_rows();
+        let mut bytes = vec![0; num_cells.div_ceil(cells_per_byte)];
+        for y in 0..self.num_rows() {
+            for x in 0..self.num_cols() {
+                let i = x + y * self.num_cols();
+                let shift = (i % cells_per_byte) * bits;
+                bytes[i / cells_per_byte] |= encode_cell(self.cell(x, y)) << shift;
+            }
+        }
+        bytes
+    }
+
+    fn from_compact(cols: usize, rows: usize, bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let bits = compact_cell_bits::<P>();
+        let cells_per_byte = 8 / bits;
+        let num_cells = cols * rows;
+        if bytes.len() != num_cells.div_ceil(cells_per_byte) {
+            return Err("invalid length".into());
+        }
+        let mask = ((1u16 << bits) - 1) as u8;
+        let mut cells = Vec::with_capacity(num_cells);
+        for i in 0..num_cells {
+            let shift = (i % cells_per_byte) * bits;
+            match decode_cell((bytes[i / cells_per_byte] >> shift) & mask) {
+                Some(cell) => cells.push(cell),
+                None => return Err("invalid cell".into()),
+            }
+        }
+        Ok(PieceGrid::new(cols, rows, cells))
+    }
+}
+
 //--- GameParams, GameLogic, GameConfig
 
 /// G = cells / frame
@@ -158,6 +381,16 @@ impl Default for LockDelayReset {
     }
 }
 
+/// How cells above cleared lines fall.
+#[derive(Debug, Copy, Clone, Default)]
+pub enum LineClearGravity {
+    /// Rows above cleared lines drop as whole rows.
+    #[default]
+    Naive,
+    /// Each cell drops individually to fill gaps below.
+    Cascade,
+}
+
 bitflags! {
     /// http://harddrop.com/wiki/Top_out
     pub struct TopOutCondition: u32 {
@@ -175,12 +408,12 @@ impl TopOutCondition {
     ) -> TopOutCondition {
         if self.contains(TopOutCondition::LOCK_OUT) {
             if falling_piece.is_lock_out(playfield) {
-                return self;
+                return TopOutCondition::LOCK_OUT;
             }
         }
         if self.contains(TopOutCondition::PARTIAL_LOCK_OUT) {
             if falling_piece.is_partial_lock_out(playfield) {
-                return self;
+                return TopOutCondition::PARTIAL_LOCK_OUT;
             }
         }
         return Self::empty();
@@ -199,7 +432,7 @@ impl fmt::Display for TopOutCondition {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum GameOverReason {
     BlockOut,
     LockOut,
@@ -222,12 +455,50 @@ impl From<TopOutCondition> for Option<GameOverReason> {
     }
 }
 
+/// Line clear delay keyed by the number of cleared lines and T-Spin.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LineClearDelay {
+    /// Delays for 1-4 lines without T-Spin.
+    pub normal: [Frames; 4],
+    /// Delays for 1-4 lines with T-Spin (including Mini).
+    pub tspin: [Frames; 4],
+}
+
+impl LineClearDelay {
+    pub fn uniform(delay: Frames) -> Self {
+        Self {
+            normal: [delay; 4],
+            tspin: [delay; 4],
+        }
+    }
+    pub fn get(&self, num_lines: usize, tspin: TSpin) -> Frames {
+        if num_lines == 0 {
+            return 0;
+        }
+        let idx = core::cmp::min(num_lines, 4) - 1;
+        if tspin == TSpin::None {
+            self.normal[idx]
+        } else {
+            self.tspin[idx]
+        }
+    }
+}
+
+impl From<Frames> for LineClearDelay {
+    fn from(delay: Frames) -> Self {
+        Self::uniform(delay)
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct GameParams {
     pub gravity: Gravity,
     pub soft_drop_gravity: Gravity,
     pub lock_delay: Frames,
     pub lock_delay_reset: LockDelayReset,
+    /// The max number of lock delay resets by moves or rotations per piece
+    /// with `LockDelayReset::MoveReset`.
+    pub max_lock_delay_resets: usize,
     /// https://harddrop.com/wiki/Lock_delay
     pub lock_delay_cancel: bool,
     // Delayed Auto Shift: https://harddrop.com/wiki/DAS
@@ -236,8 +507,35 @@ pub struct GameParams {
     pub arr: Frames,
     // https://harddrop.com/wiki/ARE
     pub are: Frames,
-    pub line_clear_delay: Frames,
+    /// ARE after a line clear. Same as `are` if None.
+    pub line_clear_are: Option<Frames>,
+    /// Frames to stay in the lock state after placing a piece, e.g. for a lock flash.
+    pub lock_flash_delay: Frames,
+    pub line_clear_delay: LineClearDelay,
+    pub line_clear_gravity: LineClearGravity,
     pub top_out_condition: TopOutCondition,
+    /// Whether `Input::FIRM_DROP` is available.
+    pub firm_drop_enabled: bool,
+    /// Apply gravity before moves and rotations in a frame.
+    pub gravity_before_input: bool,
+    /// Bring the held piece back at the spawn position with the rotation when held,
+    /// instead of the spawn rotation. Spawned as usual if it does not fit.
+    pub hold_preserves_state: bool,
+    /// Whether `Input::HOLD` is available.
+    pub hold_enabled: bool,
+    /// The max number of holds per piece. `None` for once, `Some(0)` for unlimited.
+    pub hold_limit: Option<u32>,
+    /// Resolve opposite inputs before handled. `None` to pass them through.
+    pub input_conflict_policy: Option<ConflictPolicy>,
+    /// Charge DAS during ARE so that a held direction auto-shifts as soon as the piece spawns.
+    /// A tap released before DAS is charged is not buffered.
+    pub das_during_are: bool,
+    /// Whether the ghost piece is shown, cf. `GameData::ghost_piece()`.
+    pub ghost_piece_enabled: bool,
+    /// Finish the game when the total number of cleared lines reaches it, e.g. 40 for Sprint.
+    pub line_goal: Option<usize>,
+    /// Finish the game when `GameData::elapsed_frames` reaches it, e.g. 2 minutes for Ultra.
+    pub time_limit: Option<Frames>,
 }
 
 impl Default for GameParams {
@@ -247,12 +545,71 @@ impl Default for GameParams {
             soft_drop_gravity: 1.0,
             lock_delay: 60,
             lock_delay_reset: LockDelayReset::default(),
+            max_lock_delay_resets: 15,
             lock_delay_cancel: true,
             das: 11,
             arr: 2,
             are: 40,
-            line_clear_delay: 40,
+            line_clear_are: None,
+            lock_flash_delay: 0,
+            line_clear_delay: 40.into(),
+            line_clear_gravity: LineClearGravity::default(),
             top_out_condition: TopOutCondition::default(),
+            firm_drop_enabled: true,
+            gravity_before_input: false,
+            hold_preserves_state: false,
+            hold_enabled: true,
+            hold_limit: None,
+            input_conflict_policy: None,
+            das_during_are: false,
+            ghost_piece_enabled: true,
+            line_goal: None,
+            time_limit: None,
+        }
+    }
+}
+
+impl GameParams {
+    /// Whether hold is available after `num_holds` holds for the current piece.
+    pub fn can_hold(&self, num_holds: u32) -> bool {
+        let limit = self.hold_limit.unwrap_or(1);
+        self.hold_enabled && (limit == 0 || num_holds < limit)
+    }
+
+    /// Baseline params based on the Tetris Guideline.
+    ///
+    /// * gravity: 1/60 G (level 1)
+    /// * soft drop: 20 times faster than gravity
+    /// * lock delay: 30 frames (0.5 sec) with move reset up to 15 times
+    /// * DAS/ARR: 10/2 frames
+    /// * ARE: 6 frames
+    /// * line clear delay: 30 frames
+    pub fn guideline() -> Self {
+        GameParams {
+            gravity: 1.0 / 60.0,
+            soft_drop_gravity: 20.0 / 60.0,
+            lock_delay: 30,
+            lock_delay_reset: LockDelayReset::MoveReset,
+            max_lock_delay_resets: 15,
+            lock_delay_cancel: true,
+            das: 10,
+            arr: 2,
+            are: 6,
+            line_clear_are: None,
+            lock_flash_delay: 0,
+            line_clear_delay: 30.into(),
+            line_clear_gravity: LineClearGravity::default(),
+            top_out_condition: TopOutCondition::default(),
+            firm_drop_enabled: true,
+            gravity_before_input: false,
+            hold_preserves_state: false,
+            hold_enabled: true,
+            hold_limit: None,
+            input_conflict_policy: None,
+            das_during_are: false,
+            ghost_piece_enabled: true,
+            line_goal: None,
+            time_limit: None,
         }
     }
 }
@@ -270,17 +627,133 @@ impl Default for TSpin {
     }
 }
 
+/// Result of a successful rotation.
+#[derive(Debug, Copy, Clone)]
+pub struct RotationResult<P: Piece> {
+    pub falling_piece: FallingPiece<P>,
+    pub tspin: TSpin,
+    /// Index of the kick offset used for the rotation. 0 means no kick.
+    pub kick_index: usize,
+}
+
+/// Deterministic random number generator (SplitMix64) for game logics without external crates.
+/// cf. `GameData::rng`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GameRng {
+    state: u64,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+    /// Return a number in `0..n`. Slightly biased but enough for games.
+    pub fn gen_below(&mut self, n: usize) -> usize {
+        assert!(n > 0);
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// Why a rotation failed, cf. `GameLogic::try_rotate()`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RotateFailure {
+    /// Union of the overlay results of all tried positions, e.g. only `OVERFLOW` if blocked
+    /// by walls. Empty if rejected by other rules like `GameLogic::is_movable_to()`
+    /// or if the logic does not override `GameLogic::rotation_candidates()`.
+    pub overlay: grid::OverlayResult,
+}
+
+/// Result of spawning a piece.
+#[derive(Debug, Copy, Clone)]
+pub enum SpawnResult<P: Piece> {
+    /// Placed at the spawn position.
+    Placed(FallingPiece<P>),
+    /// Placed one row higher than the spawn position because it was blocked.
+    NudgedUp(FallingPiece<P>),
+    /// Can not be placed (block out).
+    Blocked,
+}
+
+impl<P: Piece> SpawnResult<P> {
+    pub fn falling_piece(&self) -> Option<FallingPiece<P>> {
+        match self {
+            SpawnResult::Placed(fp) | SpawnResult::NudgedUp(fp) => Some(*fp),
+            SpawnResult::Blocked => None,
+        }
+    }
+}
+
 pub trait GameLogic<P: Piece>: fmt::Debug {
+    /// Rotation of a newly spawned piece.
+    fn spawn_rotation(&self, _piece: P) -> Rotation {
+        Rotation::default()
+    }
+    /// Columns added to the centered x of a newly spawned piece.
+    fn spawn_x_offset(&self, _piece: P) -> i32 {
+        0
+    }
     /// Create new falling piece at initial position.
-    fn spawn_piece(&self, piece: P, playfield: &Playfield<P>) -> FallingPiece<P>;
-    /// Rotate `falling_piece` on `playfield` by `cw`.
-    /// If not rotatable, return None.
+    /// If the piece can not be put onto `playfield` (block out), return `SpawnResult::Blocked`.
+    fn spawn_piece(&self, piece: P, playfield: &Playfield<P>) -> SpawnResult<P>;
+    /// Rotate `falling_piece` on `playfield` by `cw`, trying kicks until one satisfies
+    /// `is_movable_to`. If not rotatable, return None.
     fn rotate(
         &self,
         cw: bool,
         falling_piece: &FallingPiece<P>,
         playfield: &Playfield<P>,
-    ) -> Option<(FallingPiece<P>, TSpin)>;
+    ) -> Option<RotationResult<P>>;
+    /// Return all positions tried by `rotate` in the order, paired with whether each fits
+    /// `playfield`. By default, only the position `rotate` returns if any.
+    fn rotation_candidates(
+        &self,
+        cw: bool,
+        falling_piece: &FallingPiece<P>,
+        playfield: &Playfield<P>,
+    ) -> Vec<(FallingPiece<P>, bool)> {
+        self.rotate(cw, falling_piece, playfield)
+            .map(|r| vec![(r.falling_piece, true)])
+            .unwrap_or_default()
+    }
+    /// Same as `rotate` but return why it failed if not rotatable.
+    fn try_rotate(
+        &self,
+        cw: bool,
+        falling_piece: &FallingPiece<P>,
+        playfield: &Playfield<P>,
+    ) -> Result<RotationResult<P>, RotateFailure> {
+        if let Some(r) = self.rotate(cw, falling_piece, playfield) {
+            return Ok(r);
+        }
+        let overlay = self
+            .rotation_candidates(cw, falling_piece, playfield)
+            .iter()
+            .fold(grid::OverlayResult::empty(), |acc, (fp, _)| {
+                acc | playfield.grid.check_overlay(fp.x, fp.y, fp.grid())
+            });
+        Err(RotateFailure { overlay })
+    }
+    /// Called when `falling_piece` is put onto `playfield` before checking line clears,
+    /// e.g. to add random garbage. `rng` is seeded from `GameData::seed`.
+    fn after_lock(
+        &self,
+        _falling_piece: &FallingPiece<P>,
+        _playfield: &mut Playfield<P>,
+        _rng: &mut GameRng,
+    ) {
+    }
+    /// Whether `falling_piece` can be moved or rotated to its position on `playfield`.
+    /// Override to add restrictions like walls only for the falling piece.
+    fn is_movable_to(&self, falling_piece: &FallingPiece<P>, playfield: &Playfield<P>) -> bool {
+        falling_piece.can_put_onto(playfield)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -324,6 +797,68 @@ const INPUTS: [Input; 8] = [
     Input::HOLD,
 ];
 
+const INPUT_TOKENS: [&str; 8] = ["HD", "SD", "FD", "ML", "MR", "RCW", "RCCW", "H"];
+
+impl Input {
+    /// Return a compact string like "HD" or "ML|RCW". Empty input is "".
+    pub fn to_token(&self) -> String {
+        let mut tokens = Vec::new();
+        for (input, token) in INPUTS.iter().zip(INPUT_TOKENS.iter()) {
+            if self.contains(*input) {
+                tokens.push(*token);
+            }
+        }
+        tokens.join("|")
+    }
+
+    /// Parse a string generated by `to_token`.
+    pub fn from_token(s: &str) -> Result<Input, String> {
+        let mut input = Input::empty();
+        if s.is_empty() {
+            return Ok(input);
+        }
+        for t in s.split('|') {
+            match INPUT_TOKENS.iter().position(|token| *token == t) {
+                Some(i) => input |= INPUTS[i],
+                None => return Err(format!("unknown input token: {}", t)),
+            }
+        }
+        Ok(input)
+    }
+
+    /// Remove opposite inputs pressed together, i.e. MOVE_LEFT and MOVE_RIGHT,
+    /// or ROTATE_CW and ROTATE_CCW, according to `policy`.
+    pub fn resolve_conflicts(&self, policy: ConflictPolicy) -> Input {
+        let mut input = *self;
+        for &(first, second) in &[
+            (Input::MOVE_LEFT, Input::MOVE_RIGHT),
+            (Input::ROTATE_CW, Input::ROTATE_CCW),
+        ] {
+            if !input.contains(first | second) {
+                continue;
+            }
+            match policy {
+                ConflictPolicy::PreferLeft => input.remove(second),
+                ConflictPolicy::PreferRight => input.remove(first),
+                ConflictPolicy::Cancel => input.remove(first | second),
+            }
+        }
+        input
+    }
+}
+
+/// How to resolve opposite inputs pressed together, cf. `Input::resolve_conflicts()`.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum ConflictPolicy {
+    /// Keep MOVE_LEFT and ROTATE_CW.
+    #[default]
+    PreferLeft,
+    /// Keep MOVE_RIGHT and ROTATE_CCW.
+    PreferRight,
+    /// Drop both.
+    Cancel,
+}
+
 pub struct InputIterator {
     input: Input,
     next_idx: usize,
@@ -368,29 +903,59 @@ impl Contains<Input> for Input {
     }
 }
 
+/// Transform raw inputs into effective ones, e.g. to bind a macro to a single input.
+/// Inputs not in the table pass through.
+#[derive(Debug, Clone, Default)]
+pub struct InputMapper {
+    table: Vec<(Input, Input)>,
+}
+
+impl InputMapper {
+    /// Replace `from` with `to`. `to` can be a combination of inputs.
+    pub fn map(&mut self, from: Input, to: Input) -> &mut Self {
+        self.table.retain(|(f, _)| *f != from);
+        self.table.push((from, to));
+        self
+    }
+    pub fn apply(&self, raw: Input) -> Input {
+        let mut input = raw;
+        for (from, _) in &self.table {
+            if raw.contains(*from) {
+                input.remove(*from);
+            }
+        }
+        for (from, to) in &self.table {
+            if raw.contains(*from) {
+                input |= *to;
+            }
+        }
+        input
+    }
+}
+
 pub fn create_basic_input_manager(das: Frames, arr: Frames) -> InputManager<Input, Frames> {
     let mut mgr = InputManager::default();
-    mgr.register(Input::HARD_DROP, InputCounter::new(0, 0));
-    mgr.register(Input::SOFT_DROP, InputCounter::new(0, 1));
-    mgr.register(Input::FIRM_DROP, InputCounter::new(0, 0));
-    mgr.register(Input::MOVE_LEFT, InputCounter::new(das, arr));
-    mgr.register(Input::MOVE_RIGHT, InputCounter::new(das, arr));
-    mgr.register(Input::ROTATE_CW, InputCounter::new(0, 0));
-    mgr.register(Input::ROTATE_CCW, InputCounter::new(0, 0));
-    mgr.register(Input::HOLD, InputCounter::new(0, 0));
+    mgr.register(Input::HARD_DROP, InputCounter::one_shot());
+    mgr.register(Input::SOFT_DROP, InputCounter::per_frame());
+    mgr.register(Input::FIRM_DROP, InputCounter::one_shot());
+    mgr.register(Input::MOVE_LEFT, InputCounter::auto_repeat(das, arr));
+    mgr.register(Input::MOVE_RIGHT, InputCounter::auto_repeat(das, arr));
+    mgr.register(Input::ROTATE_CW, InputCounter::one_shot());
+    mgr.register(Input::ROTATE_CCW, InputCounter::one_shot());
+    mgr.register(Input::HOLD, InputCounter::one_shot());
     mgr
 }
 
 pub fn create_input_manager_for_automation() -> InputManager<Input, Frames> {
     let mut mgr = InputManager::default();
-    mgr.register(Input::HARD_DROP, InputCounter::new(1, 0));
-    mgr.register(Input::SOFT_DROP, InputCounter::new(1, 0));
-    mgr.register(Input::FIRM_DROP, InputCounter::new(1, 0));
-    mgr.register(Input::MOVE_LEFT, InputCounter::new(1, 0));
-    mgr.register(Input::MOVE_RIGHT, InputCounter::new(1, 0));
-    mgr.register(Input::ROTATE_CW, InputCounter::new(1, 0));
-    mgr.register(Input::ROTATE_CCW, InputCounter::new(1, 0));
-    mgr.register(Input::HOLD, InputCounter::new(1, 0));
+    mgr.register(Input::HARD_DROP, InputCounter::per_frame());
+    mgr.register(Input::SOFT_DROP, InputCounter::per_frame());
+    mgr.register(Input::FIRM_DROP, InputCounter::per_frame());
+    mgr.register(Input::MOVE_LEFT, InputCounter::per_frame());
+    mgr.register(Input::MOVE_RIGHT, InputCounter::per_frame());
+    mgr.register(Input::ROTATE_CW, InputCounter::per_frame());
+    mgr.register(Input::ROTATE_CCW, InputCounter::per_frame());
+    mgr.register(Input::HOLD, InputCounter::per_frame());
     mgr
 }
 
@@ -401,6 +966,23 @@ pub enum GameEvent {
     Update(Input),
     LineCleared(usize, TSpin),
     EnterState(GameStateId),
+    GameOver(GameOverReason),
+    /// Garbage lines sent by a line clear.
+    AttackSent(usize),
+    /// HOLD was input while the hold limit was reached for the current piece.
+    HoldRejected,
+    /// The number of rows dropped by soft drop or firm drop.
+    SoftDropped(usize),
+    /// Emitted on each frame of the line clear delay, `frame` counting from 1 to `total`.
+    LineClearProgress {
+        rows: Vec<usize>,
+        frame: Frames,
+        total: Frames,
+    },
+    /// Reached `GameParams::line_goal` or `GameParams::time_limit`.
+    GoalReached {
+        frames: Frames,
+    },
 }
 
 //--- GameData
@@ -413,6 +995,23 @@ pub struct GameData<P: Piece> {
     pub next_pieces: VecDeque<P>,
     pub input_manager: InputManager<Input, Frames>,
     pub tspin: TSpin,
+    /// The number of consecutive line clears - 1, or -1 if not in combo.
+    pub combo: i32,
+    /// Whether the last line clear was a Tetris or T-Spin.
+    pub back_to_back: bool,
+    /// The number of holds for the current piece.
+    pub num_holds_this_piece: u32,
+    /// The held piece as it was when held, with `GameParams::hold_preserves_state`.
+    /// Only the rotation is brought back.
+    pub held_falling_piece: Option<FallingPiece<P>>,
+    /// The seed of the randomizer generating the pieces, if any. Only for records.
+    pub seed: Option<u64>,
+    /// Passed to `GameLogic::after_lock()`. Reseeded from `seed` (0 if None) in `Game::new()`.
+    pub rng: GameRng,
+    /// The total number of cleared lines.
+    pub num_cleared_lines: usize,
+    /// Frames elapsed since the game started, stopped when the game ends.
+    pub elapsed_frames: Frames,
     pub events: Vec<GameEvent>,
 }
 
@@ -431,9 +1030,128 @@ impl<P: Piece> GameData<P> {
             next_pieces: next_pieces,
             input_manager: create_basic_input_manager(params.das, params.arr),
             tspin: TSpin::None,
+            combo: -1,
+            back_to_back: false,
+            num_holds_this_piece: 0,
+            held_falling_piece: None,
+            seed: None,
+            rng: GameRng::new(0),
+            num_cleared_lines: 0,
+            elapsed_frames: 0,
             events: Vec::new(),
         }
     }
+
+    /// Whether hold was already used for the current piece.
+    pub fn is_hold_used(&self) -> bool {
+        self.num_holds_this_piece > 0
+    }
+
+    /// Return the cell to display at (x, y) of the playfield.
+    /// The falling piece (`Cell::Block`) takes precedence over the ghost piece (`Cell::Ghost`),
+    /// and the ghost piece over the playfield.
+    pub fn display_cell(&self, x: usize, y: usize, params: &GameParams) -> Cell<P> {
+        if let Some(fp) = self.falling_piece {
+            if let Some(c) = piece_cell(&fp, x, y) {
+                return c;
+            }
+        }
+        if let Some(gp) = self.ghost_piece(params) {
+            if let Some(Cell::Block(p)) = piece_cell(&gp, x, y) {
+                return Cell::Ghost(p);
+            }
+        }
+        self.playfield.grid.cell(x, y)
+    }
+
+    /// Return the falling piece dropped to the bottom, or None if disabled by `params`.
+    pub fn ghost_piece(&self, params: &GameParams) -> Option<FallingPiece<P>> {
+        if !params.ghost_piece_enabled {
+            return None;
+        }
+        let mut gp = self.falling_piece?;
+        gp.y -= gp.droppable_rows(&self.playfield) as i32;
+        Some(gp)
+    }
+}
+
+/// The number of visible rows of playfields parsed by `GameData::from_board_string()`.
+const BOARD_STRING_VISIBLE_ROWS: usize = 20;
+
+impl<P: Piece + fmt::Display + FromStr> GameData<P> {
+    /// Return the playfield as text for snapshot tests, one line per row from the top.
+    /// Each cell is the piece letter, `x` for garbage or `.` for empty.
+    /// The falling piece and ghosts are not included.
+    pub fn to_board_string(&self) -> String {
+        let grid = &self.playfield.grid;
+        let mut s = String::with_capacity((grid.num_cols() + 1) * grid.num_rows());
+        for row in grid.rows_top_down() {
+            for cell in row {
+                match cell {
+                    Cell::Block(p) => s.push_str(&format!("{}", p)),
+                    Cell::Garbage => s.push('x'),
+                    Cell::Empty | Cell::Ghost(_) => s.push('.'),
+                }
+            }
+            s.push('\n');
+        }
+        s
+    }
+
+    /// Parse a string generated by `to_board_string`. The bottom 20 rows are visible.
+    pub fn from_board_string(
+        s: &str,
+        next_pieces: VecDeque<P>,
+        hold_piece: Option<P>,
+    ) -> Result<Self, String> {
+        let mut rows = Vec::new();
+        for line in s.lines() {
+            let mut row = Vec::with_capacity(line.len());
+            for c in line.chars() {
+                let cell = match c {
+                    '.' => Cell::Empty,
+                    'x' => Cell::Garbage,
+                    _ => match P::from_str(c.encode_utf8(&mut [0; 4])) {
+                        Ok(p) => Cell::Block(p),
+                        Err(_) => return Err(format!("invalid cell: {}", c)),
+                    },
+                };
+                row.push(cell);
+            }
+            if rows
+                .first()
+                .is_some_and(|r: &Vec<Cell<P>>| r.len() != row.len())
+            {
+                return Err("inconsistent row width".into());
+            }
+            rows.push(row);
+        }
+        let grid = grid::Grid::from_rows(rows);
+        let playfield = Playfield {
+            visible_rows: grid.num_rows().min(BOARD_STRING_VISIBLE_ROWS),
+            grid,
+        };
+        Ok(Self::new(
+            playfield,
+            None,
+            hold_piece,
+            next_pieces,
+            &GameParams::default(),
+        ))
+    }
+}
+
+/// Return the block cell of `fp` at (x, y) of the playfield if exists.
+fn piece_cell<P: Piece>(fp: &FallingPiece<P>, x: usize, y: usize) -> Option<Cell<P>> {
+    let x = x as i32 - fp.x;
+    let y = y as i32 - fp.y;
+    if x < 0 || y < 0 {
+        return None;
+    }
+    match fp.grid().try_cell(x as usize, y as usize) {
+        Some(Cell::Block(p)) => Some(Cell::Block(p)),
+        _ => None,
+    }
 }
 
 //--- GameState
@@ -446,9 +1164,61 @@ pub enum GameStateId {
     LineClear,
     SpawnPiece,
     GameOver,
+    /// Reached the goal, cf. `GameParams::line_goal` and `GameParams::time_limit`.
+    Finished,
     Error,
 }
 
+/// Allowed state transitions, checked on every transition for debugging.
+/// cf. `Game::set_transition_validator()`.
+#[derive(Debug, Clone, Default)]
+pub struct TransitionValidator {
+    allowed: Vec<(GameStateId, GameStateId)>,
+}
+
+impl TransitionValidator {
+    /// The transitions of the built-in states.
+    /// Any ongoing state may end by `Game::place()` or `GameParams::time_limit`.
+    pub fn standard() -> Self {
+        use GameStateId::*;
+        let mut v = Self::default();
+        v.allow(Init, Play)
+            .allow(Init, SpawnPiece)
+            .allow(Play, Lock)
+            .allow(Lock, LineClear)
+            .allow(Lock, SpawnPiece)
+            .allow(LineClear, SpawnPiece)
+            .allow(SpawnPiece, Play);
+        for &from in &[Init, Play, Lock, LineClear, SpawnPiece] {
+            v.allow(from, GameOver).allow(from, Finished);
+        }
+        v
+    }
+    pub fn allow(&mut self, from: GameStateId, to: GameStateId) -> &mut Self {
+        if !self.is_allowed(from, to) {
+            self.allowed.push((from, to));
+        }
+        self
+    }
+    /// Transitions to `GameStateId::Error` are always allowed.
+    pub fn is_allowed(&self, from: GameStateId, to: GameStateId) -> bool {
+        to == GameStateId::Error || self.allowed.contains(&(from, to))
+    }
+}
+
+/// State specific progress, cf. `Game::state_snapshot()`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateSnapshot {
+    Init,
+    Play { lock_delay_remaining: Frames },
+    Lock,
+    LineClear { remaining_frames: Frames },
+    SpawnPiece { remaining_frames: Frames },
+    GameOver(GameOverReason),
+    Finished,
+    Error(String),
+}
+
 /// cf. https://stackoverflow.com/a/30353928
 trait GameStateClone<P, L> {
     fn clone_box(&self) -> Box<dyn GameState<P, L>>;
@@ -456,6 +1226,7 @@ trait GameStateClone<P, L> {
 
 trait GameState<P: Piece, L>: fmt::Debug + GameStateClone<P, L> {
     fn id(&self) -> GameStateId;
+    fn snapshot(&self, config: &GameConfig<L>) -> StateSnapshot;
     fn should_update_input_manager(&self) -> bool {
         false
     }
@@ -500,6 +1271,9 @@ impl<P: Piece, L: GameLogic<P>> GameState<P, L> for GameStateError {
     fn id(&self) -> GameStateId {
         GameStateId::Error
     }
+    fn snapshot(&self, _config: &GameConfig<L>) -> StateSnapshot {
+        StateSnapshot::Error(self.reason.clone())
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -509,6 +1283,9 @@ impl<P: Piece, L: GameLogic<P>> GameState<P, L> for GameStateInit {
     fn id(&self) -> GameStateId {
         GameStateId::Init
     }
+    fn snapshot(&self, _config: &GameConfig<L>) -> StateSnapshot {
+        StateSnapshot::Init
+    }
     fn update(
         &mut self,
         data: &mut GameData<P>,
@@ -526,13 +1303,44 @@ impl<P: Piece, L: GameLogic<P>> GameState<P, L> for GameStateInit {
 struct GameStatePlay {
     gravity_counter: Gravity,
     lock_delay_counter: Frames,
-    is_piece_held: bool,
+    num_lock_delay_resets: usize,
+}
+
+impl GameStatePlay {
+    /// Drop `fp` by the accumulated gravity, then return the number of dropped rows.
+    fn apply_gravity<P: Piece>(
+        &mut self,
+        fp: &mut FallingPiece<P>,
+        playfield: &Playfield<P>,
+    ) -> usize {
+        let num_droppable_rows = fp.droppable_rows(playfield);
+        if num_droppable_rows == 0 {
+            self.gravity_counter = 0.0;
+            return 0;
+        }
+        if self.gravity_counter < 1.0 {
+            return 0;
+        }
+        let n = core::cmp::min(num_droppable_rows, self.gravity_counter as usize);
+        fp.y -= n as i32;
+        self.gravity_counter = 0.0;
+        self.lock_delay_counter = 0;
+        n
+    }
 }
 
 impl<P: Piece, L: GameLogic<P>> GameState<P, L> for GameStatePlay {
     fn id(&self) -> GameStateId {
         GameStateId::Play
     }
+    fn snapshot(&self, config: &GameConfig<L>) -> StateSnapshot {
+        StateSnapshot::Play {
+            lock_delay_remaining: config
+                .params
+                .lock_delay
+                .saturating_sub(self.lock_delay_counter),
+        }
+    }
     fn should_update_input_manager(&self) -> bool {
         true
     }
@@ -555,6 +1363,7 @@ impl<P: Piece, L: GameLogic<P>> GameState<P, L> for GameStatePlay {
         let fp = data.falling_piece.as_mut().unwrap();
         let playfield = &data.playfield;
         let num_droppable_rows = fp.droppable_rows(playfield);
+        let mut is_soft_dropping = false;
 
         // HARD_DROP
         if input_mgr.handle(Input::HARD_DROP) {
@@ -563,28 +1372,51 @@ impl<P: Piece, L: GameLogic<P>> GameState<P, L> for GameStatePlay {
         }
 
         // HOLD
-        if !self.is_piece_held && input_mgr.handle(Input::HOLD) {
-            self.is_piece_held = true;
-            let np = if let Some(p) = data.hold_piece {
-                p
+        if input_mgr.handle(Input::HOLD) && config.params.hold_enabled {
+            if !config.params.can_hold(data.num_holds_this_piece) {
+                // Fall through to the others not to freeze the piece for this frame.
+                data.events.push(GameEvent::HoldRejected);
             } else {
-                if data.next_pieces.is_empty() {
-                    return Err("no next pieces".into());
-                }
-                data.next_pieces.pop_front().unwrap()
-            };
-            let sfp = config.logic.spawn_piece(np, playfield);
-            if !sfp.can_put_onto(playfield) {
-                return Ok(Some(Box::new(GameStateGameOver::new(
-                    GameOverReason::BlockOut,
-                ))));
-            }
-            data.hold_piece = Some(fp.piece);
-            data.falling_piece = Some(sfp);
-            data.tspin = TSpin::None;
-            self.gravity_counter = 0.0;
-            self.lock_delay_counter = 0;
-            return Ok(None);
+                data.num_holds_this_piece += 1;
+                let np = if let Some(p) = data.hold_piece {
+                    p
+                } else {
+                    if data.next_pieces.is_empty() {
+                        return Err("no next pieces".into());
+                    }
+                    data.next_pieces.pop_front().unwrap()
+                };
+                let is_swap = data.hold_piece.is_some();
+                let spawned = config.logic.spawn_piece(np, playfield).falling_piece();
+                let preserved = match (spawned, data.held_falling_piece) {
+                    (Some(sfp), Some(hfp)) if is_swap => Some(FallingPiece {
+                        rotation: hfp.rotation,
+                        ..sfp
+                    })
+                    .filter(|fp| config.logic.is_movable_to(fp, playfield)),
+                    _ => None,
+                };
+                let sfp = match preserved.or(spawned) {
+                    Some(sfp) => sfp,
+                    None => {
+                        return Ok(Some(Box::new(GameStateGameOver::new(
+                            GameOverReason::BlockOut,
+                        ))))
+                    }
+                };
+                data.hold_piece = Some(fp.piece);
+                data.held_falling_piece = if config.params.hold_preserves_state {
+                    Some(*fp)
+                } else {
+                    None
+                };
+                data.falling_piece = Some(sfp);
+                data.tspin = TSpin::None;
+                self.gravity_counter = 0.0;
+                self.lock_delay_counter = 0;
+                self.num_lock_delay_resets = 0;
+                return Ok(None);
+            }
         }
 
         // Others
@@ -598,8 +1430,9 @@ impl<P: Piece, L: GameLogic<P>> GameState<P, L> for GameStatePlay {
             if should_lock {
                 return Ok(Some(Box::new(GameStateLock::new())));
             }
-        } else if input_mgr.handle(Input::FIRM_DROP) {
+        } else if config.params.firm_drop_enabled && input_mgr.handle(Input::FIRM_DROP) {
             fp.y -= num_droppable_rows as i32;
+            data.events.push(GameEvent::SoftDropped(num_droppable_rows));
             data.tspin = TSpin::None;
             self.gravity_counter = 0.0;
             self.lock_delay_counter = 0;
@@ -608,9 +1441,20 @@ impl<P: Piece, L: GameLogic<P>> GameState<P, L> for GameStatePlay {
             self.gravity_counter += config.params.gravity;
             if input_mgr.handle(Input::SOFT_DROP) {
                 self.gravity_counter += config.params.soft_drop_gravity;
+                is_soft_dropping = true;
             }
         }
         let mut moved = fp.clone();
+        let mut is_moved = false;
+        if config.params.gravity_before_input {
+            let n = self.apply_gravity(&mut moved, playfield);
+            if n > 0 {
+                if is_soft_dropping {
+                    data.events.push(GameEvent::SoftDropped(n));
+                }
+                data.tspin = TSpin::None;
+            }
+        }
         let dx = if input_mgr.handle(Input::MOVE_LEFT) {
             -1
         } else if input_mgr.handle(Input::MOVE_RIGHT) {
@@ -619,10 +1463,12 @@ impl<P: Piece, L: GameLogic<P>> GameState<P, L> for GameStatePlay {
             0
         };
         if dx != 0 {
-            let mut t = moved;
-            t.x += dx;
-            if t.can_put_onto(playfield) {
+            if let Some(t) = moved
+                .try_move(dx, 0, playfield)
+                .filter(|t| config.logic.is_movable_to(t, playfield))
+            {
                 moved = t;
+                is_moved = true;
                 data.tspin = TSpin::None;
             }
         }
@@ -634,31 +1480,43 @@ impl<P: Piece, L: GameLogic<P>> GameState<P, L> for GameStatePlay {
             (false, false)
         };
         if rotate.0 {
-            if let Some(r) = config.logic.rotate(rotate.1, &moved, playfield) {
-                moved = r.0;
-                data.tspin = r.1;
+            if let Some(r) = moved.try_rotate(rotate.1, &config.logic, playfield) {
+                moved = r.falling_piece;
+                is_moved = true;
+                data.tspin = r.tspin;
             }
         }
-        let num_droppable_rows = moved.droppable_rows(playfield);
-        if num_droppable_rows == 0 {
-            self.gravity_counter = 0.0;
-        } else if self.gravity_counter >= 1.0 {
-            moved.y -= std::cmp::min(num_droppable_rows, self.gravity_counter as usize) as i32;
-            data.tspin = TSpin::None;
-            self.gravity_counter = 0.0;
+        if is_moved
+            && self.lock_delay_counter > 0
+            && matches!(config.params.lock_delay_reset, LockDelayReset::MoveReset)
+            && self.num_lock_delay_resets < config.params.max_lock_delay_resets
+        {
             self.lock_delay_counter = 0;
+            self.num_lock_delay_resets += 1;
+        }
+        if !config.params.gravity_before_input {
+            let n = self.apply_gravity(&mut moved, playfield);
+            if n > 0 {
+                if is_soft_dropping {
+                    data.events.push(GameEvent::SoftDropped(n));
+                }
+                data.tspin = TSpin::None;
+            }
         }
         data.falling_piece = Some(moved);
         Ok(None)
     }
 }
 
-#[derive(Debug, Copy, Clone)]
-struct GameStateLock {}
+#[derive(Debug, Copy, Clone, Default)]
+struct GameStateLock {
+    frame_count: Frames,
+    has_filled_rows: bool,
+}
 
 impl GameStateLock {
     fn new() -> Self {
-        Self {}
+        Self::default()
     }
 
     fn lock<P: Piece, L: GameLogic<P>>(
@@ -674,16 +1532,19 @@ impl GameStateLock {
         }
         let r = fp.put_onto(&mut data.playfield);
         assert!(r.is_empty());
-        for y in 0..data.playfield.visible_rows {
-            if data.playfield.grid.is_row_filled(y) {
-                return Ok(Some(Box::new(GameStateLineClear::default())));
+        config
+            .logic
+            .after_lock(fp, &mut data.playfield, &mut data.rng);
+        self.has_filled_rows =
+            (0..data.playfield.visible_rows).any(|y| data.playfield.grid.is_row_filled(y));
+        if !self.has_filled_rows {
+            data.combo = -1;
+            if data.tspin == TSpin::Mini {
+                // T-Spin (Mini) Zero
+                data.events.push(GameEvent::LineCleared(0, TSpin::Mini));
             }
         }
-        if data.tspin == TSpin::Mini {
-            // T-Spin (Mini) Zero
-            data.events.push(GameEvent::LineCleared(0, TSpin::Mini));
-        }
-        Ok(Some(Box::new(GameStateSpawnPiece::default())))
+        Ok(None)
     }
 }
 
@@ -691,6 +1552,9 @@ impl<P: Piece, L: GameLogic<P>> GameState<P, L> for GameStateLock {
     fn id(&self) -> GameStateId {
         GameStateId::Lock
     }
+    fn snapshot(&self, _config: &GameConfig<L>) -> StateSnapshot {
+        StateSnapshot::Lock
+    }
     fn enter(
         &mut self,
         data: &mut GameData<P>,
@@ -707,48 +1571,140 @@ impl<P: Piece, L: GameLogic<P>> GameState<P, L> for GameStateLock {
         data: &mut GameData<P>,
         config: &GameConfig<L>,
     ) -> Result<Option<Box<dyn GameState<P, L>>>, String> {
-        self.lock(data, config)
-    }
-}
-
-#[derive(Debug, Copy, Clone, Default)]
-struct GameStateLineClear {
-    frame_count: Frames,
+        if self.frame_count == 0 {
+            if let Some(next) = self.lock(data, config)? {
+                return Ok(Some(next));
+            }
+        }
+        self.frame_count += 1;
+        if self.frame_count <= config.params.lock_flash_delay {
+            return Ok(None);
+        }
+        if self.has_filled_rows {
+            Ok(Some(Box::new(GameStateLineClear::default())))
+        } else {
+            Ok(Some(Box::new(GameStateSpawnPiece::default())))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct GameStateLineClear {
+    frame_count: Frames,
+    delay: Frames,
+    /// The indices of the cleared rows, fixed at the first frame.
+    rows: Vec<usize>,
 }
 
 impl<P: Piece, L: GameLogic<P>> GameState<P, L> for GameStateLineClear {
     fn id(&self) -> GameStateId {
         GameStateId::LineClear
     }
+    fn snapshot(&self, _config: &GameConfig<L>) -> StateSnapshot {
+        StateSnapshot::LineClear {
+            remaining_frames: self.delay.saturating_sub(self.frame_count),
+        }
+    }
     fn update(
         &mut self,
         data: &mut GameData<P>,
         config: &GameConfig<L>,
     ) -> Result<Option<Box<dyn GameState<P, L>>>, String> {
         if self.frame_count == 0 {
-            let n = data.playfield.grid.pluck_filled_rows(Some(Cell::Empty));
-            data.events.push(GameEvent::LineCleared(n, data.tspin));
+            self.rows = data.playfield.grid.filled_row_indices();
+            let n = clear_filled_rows(data, config, data.tspin);
             if n == 0 {
                 return Err("FATAL: no lines cleared".into());
             }
+            if is_line_goal_reached(data, config) {
+                return Ok(Some(Box::new(GameStateFinished)));
+            }
+            self.delay = config.params.line_clear_delay.get(n, data.tspin);
         }
         self.frame_count += 1;
-        if self.frame_count <= config.params.line_clear_delay {
+        if self.frame_count <= self.delay {
+            data.events.push(GameEvent::LineClearProgress {
+                rows: self.rows.clone(),
+                frame: self.frame_count,
+                total: self.delay,
+            });
             return Ok(None);
         }
-        Ok(Some(Box::new(GameStateSpawnPiece::default())))
+        Ok(Some(Box::new(GameStateSpawnPiece::after_line_clear())))
     }
 }
 
+/// Clear the filled rows, then update the combo, back-to-back, `num_cleared_lines` and events
+/// as cleared by `tspin`. Return the number of cleared rows, updating nothing if 0.
+fn clear_filled_rows<P: Piece, L>(
+    data: &mut GameData<P>,
+    config: &GameConfig<L>,
+    tspin: TSpin,
+) -> usize {
+    let n = data.playfield.grid.pluck_filled_rows(Some(Cell::Empty));
+    if n == 0 {
+        return 0;
+    }
+    data.events.push(GameEvent::LineCleared(n, tspin));
+    data.num_cleared_lines += n;
+    if let LineClearGravity::Cascade = config.params.line_clear_gravity {
+        data.playfield.grid.apply_cascade_gravity();
+    }
+    data.combo += 1;
+    let is_difficult = n == 4 || tspin != TSpin::None;
+    let attack = garbage::attack_lines(
+        n,
+        tspin,
+        data.combo,
+        is_difficult && data.back_to_back,
+        data.playfield.is_empty(),
+    );
+    data.back_to_back = is_difficult;
+    if attack > 0 {
+        data.events.push(GameEvent::AttackSent(attack));
+    }
+    n
+}
+
+fn is_line_goal_reached<P: Piece, L>(data: &GameData<P>, config: &GameConfig<L>) -> bool {
+    config
+        .params
+        .line_goal
+        .is_some_and(|goal| data.num_cleared_lines >= goal)
+}
+
 #[derive(Debug, Copy, Clone, Default)]
 struct GameStateSpawnPiece {
     frame_count: Frames,
+    after_line_clear: bool,
+}
+
+impl GameStateSpawnPiece {
+    fn after_line_clear() -> Self {
+        Self {
+            after_line_clear: true,
+            ..Self::default()
+        }
+    }
+
+    fn are<L>(&self, config: &GameConfig<L>) -> Frames {
+        if self.after_line_clear {
+            config.params.line_clear_are.unwrap_or(config.params.are)
+        } else {
+            config.params.are
+        }
+    }
 }
 
 impl<P: Piece, L: GameLogic<P>> GameState<P, L> for GameStateSpawnPiece {
     fn id(&self) -> GameStateId {
         GameStateId::SpawnPiece
     }
+    fn snapshot(&self, config: &GameConfig<L>) -> StateSnapshot {
+        StateSnapshot::SpawnPiece {
+            remaining_frames: self.are(config).saturating_sub(self.frame_count),
+        }
+    }
     fn should_update_input_manager(&self) -> bool {
         true
     }
@@ -759,19 +1715,35 @@ impl<P: Piece, L: GameLogic<P>> GameState<P, L> for GameStateSpawnPiece {
     ) -> Result<Option<Box<dyn GameState<P, L>>>, String> {
         if self.frame_count == 0 {
             if let Some(next) = data.next_pieces.pop_front() {
-                let fp = config.logic.spawn_piece(next, &data.playfield);
-                data.falling_piece = Some(fp);
-                if !fp.can_put_onto(&data.playfield) {
+                data.falling_piece = config
+                    .logic
+                    .spawn_piece(next, &data.playfield)
+                    .falling_piece();
+                data.num_holds_this_piece = 0;
+                if data.falling_piece.is_none() {
                     return Ok(Some(Box::new(GameStateGameOver::new(
-                        GameOverReason::LockOut,
+                        GameOverReason::BlockOut,
                     ))));
                 }
             } else {
                 return Err("no next piece found".into());
             };
         }
+        if config.params.das_during_are {
+            // Inputs are not counted while unhandled, so drop them until DAS is charged.
+            for &input in &[Input::MOVE_LEFT, Input::MOVE_RIGHT] {
+                let is_charged = data
+                    .input_manager
+                    .inputs()
+                    .get(&input)
+                    .is_some_and(|c| c.state() == InputState::Repeat);
+                if !is_charged {
+                    data.input_manager.handle(input);
+                }
+            }
+        }
         self.frame_count += 1;
-        if self.frame_count <= config.params.are {
+        if self.frame_count <= self.are(config) {
             return Ok(None);
         }
         Ok(Some(Box::new(GameStatePlay::default())))
@@ -793,25 +1765,85 @@ impl<P: Piece, L: GameLogic<P>> GameState<P, L> for GameStateGameOver {
     fn id(&self) -> GameStateId {
         GameStateId::GameOver
     }
+    fn snapshot(&self, _config: &GameConfig<L>) -> StateSnapshot {
+        StateSnapshot::GameOver(self.reason)
+    }
+    fn enter(
+        &mut self,
+        data: &mut GameData<P>,
+        _config: &GameConfig<L>,
+    ) -> Result<Option<Box<dyn GameState<P, L>>>, String> {
+        data.events.push(GameEvent::GameOver(self.reason));
+        Ok(None)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct GameStateFinished;
+
+impl<P: Piece, L: GameLogic<P>> GameState<P, L> for GameStateFinished {
+    fn id(&self) -> GameStateId {
+        GameStateId::Finished
+    }
+    fn snapshot(&self, _config: &GameConfig<L>) -> StateSnapshot {
+        StateSnapshot::Finished
+    }
+    fn enter(
+        &mut self,
+        data: &mut GameData<P>,
+        _config: &GameConfig<L>,
+    ) -> Result<Option<Box<dyn GameState<P, L>>>, String> {
+        data.events.push(GameEvent::GoalReached {
+            frames: data.elapsed_frames,
+        });
+        Ok(None)
+    }
 }
 
 //--- Game
 
+/// Frame limit of `Game::fast_forward()`, 1 minute.
+pub const FAST_FORWARD_MAX_FRAMES: usize = 3600;
+
+/// The state of `Game` at a frame to roll back to, cf. `Game::save_state()`.
+#[derive(Debug, Clone)]
+pub struct GameSnapshot<P: Piece, L> {
+    frame_num: Frames,
+    data: GameData<P>,
+    state: Box<dyn GameState<P, L>>,
+}
+
+impl<P: Piece, L> GameSnapshot<P, L> {
+    pub fn frame_num(&self) -> Frames {
+        self.frame_num
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Game<P: Piece, L> {
     config: GameConfig<L>,
     data: GameData<P>,
     frame_num: Frames,
     state: Box<dyn GameState<P, L>>,
+    event_log: VecDeque<GameEvent>,
+    /// Disabled if 0.
+    event_log_capacity: usize,
+    input_mapper: InputMapper,
+    transition_validator: Option<TransitionValidator>,
 }
 
 impl<P: Piece, L: GameLogic<P>> Game<P, L> {
-    pub fn new(config: GameConfig<L>, data: GameData<P>) -> Self {
+    pub fn new(config: GameConfig<L>, mut data: GameData<P>) -> Self {
+        data.rng = GameRng::new(data.seed.unwrap_or(0));
         Self {
             config: config,
             data: data,
             frame_num: 0,
             state: Box::new(GameStateInit {}),
+            event_log: VecDeque::new(),
+            event_log_capacity: 0,
+            input_mapper: InputMapper::default(),
+            transition_validator: None,
         }
     }
 
@@ -821,6 +1853,9 @@ impl<P: Piece, L: GameLogic<P>> Game<P, L> {
     pub fn data(&self) -> &GameData<P> {
         &self.data
     }
+    pub fn seed(&self) -> Option<u64> {
+        self.data.seed
+    }
     pub fn frame_num(&self) -> Frames {
         self.frame_num
     }
@@ -828,21 +1863,173 @@ impl<P: Piece, L: GameLogic<P>> Game<P, L> {
         self.state.id()
     }
 
+    pub fn state_snapshot(&self) -> StateSnapshot {
+        self.state.snapshot(&self.config)
+    }
+    /// True if the game ended by topping out or by reaching the goal.
+    pub fn is_game_over(&self) -> bool {
+        matches!(
+            self.state_id(),
+            GameStateId::GameOver | GameStateId::Finished
+        )
+    }
+    pub fn game_over_reason(&self) -> Option<GameOverReason> {
+        match self.state_snapshot() {
+            StateSnapshot::GameOver(reason) => Some(reason),
+            _ => None,
+        }
+    }
+
+    /// Replace params, which take effect from the next frame.
+    /// NOTE: `das` and `arr` are not applied to the current input manager.
+    pub fn set_params(&mut self, params: GameParams) {
+        self.config.params = params;
+    }
+    pub fn set_gravity(&mut self, gravity: Gravity) {
+        self.config.params.gravity = gravity;
+    }
+    pub fn set_lock_delay(&mut self, lock_delay: Frames) {
+        self.config.params.lock_delay = lock_delay;
+    }
+
     pub fn update(&mut self, input: Input) {
         self.data.events.clear();
         self.data.events.push(GameEvent::Update(input));
         self.frame_num += 1;
+        match self.state_id() {
+            GameStateId::GameOver | GameStateId::Finished | GameStateId::Error => {}
+            _ => self.data.elapsed_frames += 1,
+        }
         if self.state.should_update_input_manager() {
+            let mut input = self.input_mapper.apply(input);
+            if let Some(policy) = self.config.params.input_conflict_policy {
+                input = input.resolve_conflicts(policy);
+            }
             self.data.input_manager.update(input);
         }
         let r = self.state.update(&mut self.data, &self.config);
         self.handle_result(r);
+        if !self.is_game_over()
+            && self.state_id() != GameStateId::Error
+            && self
+                .config
+                .params
+                .time_limit
+                .is_some_and(|limit| self.data.elapsed_frames >= limit)
+        {
+            self.handle_result(Ok(Some(Box::new(GameStateFinished))));
+        }
+        if self.event_log_capacity > 0 {
+            for event in &self.data.events {
+                if self.event_log.len() == self.event_log_capacity {
+                    self.event_log.pop_front();
+                }
+                self.event_log.push_back(event.clone());
+            }
+        }
+    }
+
+    /// Inputs passed to `update` are transformed by `mapper` before handled.
+    /// `GameEvent::Update` still records the raw inputs.
+    pub fn set_input_mapper(&mut self, mapper: InputMapper) {
+        self.input_mapper = mapper;
+    }
+
+    /// Enter the error state on transitions not allowed by `validator`. Disabled if None.
+    pub fn set_transition_validator(&mut self, validator: Option<TransitionValidator>) {
+        self.transition_validator = validator;
+    }
+
+    /// Keep events across frames up to `capacity`, dropping the oldest ones.
+    /// Unlike `data().events`, they are retained until `drain_events()` is called.
+    pub fn enable_event_log(&mut self, capacity: usize) {
+        self.event_log_capacity = capacity;
+        while self.event_log.len() > capacity {
+            self.event_log.pop_front();
+        }
+    }
+
+    /// Take all events in the event log.
+    pub fn drain_events(&mut self) -> Vec<GameEvent> {
+        self.event_log.drain(..).collect()
+    }
+
+    /// Update with `input` `n` times.
+    pub fn step_n(&mut self, input: Input, n: usize) {
+        for _ in 0..n {
+            self.update(input);
+        }
+    }
+
+    /// Update with empty input until the state becomes `target`.
+    /// Return false if `max_frames` frames elapsed before reaching it.
+    pub fn update_until(&mut self, target: GameStateId, max_frames: usize) -> bool {
+        for _ in 0..max_frames {
+            if self.state_id() == target {
+                return true;
+            }
+            self.update(Input::default());
+        }
+        self.state_id() == target
+    }
+
+    /// Update with empty input while in states not accepting inputs (`Init`, `Lock`,
+    /// `LineClear` and `SpawnPiece`), then return the events of the skipped frames.
+    /// Stop at the terminal states, `GameOver` and `Finished`, too.
+    /// Stop after `FAST_FORWARD_MAX_FRAMES` frames for safety.
+    pub fn fast_forward(&mut self) -> Vec<GameEvent> {
+        let mut events = Vec::new();
+        for _ in 0..FAST_FORWARD_MAX_FRAMES {
+            match self.state_id() {
+                GameStateId::Init
+                | GameStateId::Lock
+                | GameStateId::LineClear
+                | GameStateId::SpawnPiece => {}
+                _ => break,
+            }
+            self.update(Input::default());
+            events.extend(self.data.events.iter().cloned());
+        }
+        events
+    }
+
+    /// Save the frame number, the game data and the state, e.g. for rollback.
+    /// The config, the input mapper and the event log are not included.
+    pub fn save_state(&self) -> GameSnapshot<P, L> {
+        GameSnapshot {
+            frame_num: self.frame_num,
+            data: self.data.clone(),
+            state: self.state.clone(),
+        }
+    }
+
+    pub fn load_state(&mut self, snapshot: GameSnapshot<P, L>) {
+        self.frame_num = snapshot.frame_num;
+        self.data = snapshot.data;
+        self.state = snapshot.state;
+    }
+
+    /// Load `snapshot` and update with `inputs` one by one,
+    /// e.g. to apply inputs received late.
+    pub fn resimulate(&mut self, snapshot: GameSnapshot<P, L>, inputs: &[Input]) {
+        self.load_state(snapshot);
+        for input in inputs {
+            self.update(*input);
+        }
     }
 
     fn handle_result(&mut self, result: Result<Option<Box<dyn GameState<P, L>>>, String>) {
         match result {
             Ok(maybe_next) => {
                 if let Some(next) = maybe_next {
+                    let (from, to) = (self.state.id(), next.id());
+                    if let Some(validator) = &self.transition_validator {
+                        if !validator.is_allowed(from, to) {
+                            let reason =
+                                format!("invalid state transition: {:?} -> {:?}", from, to);
+                            return self.handle_result(Err(reason));
+                        }
+                    }
                     self.state = next;
                     self.data
                         .events
@@ -860,10 +2047,2048 @@ impl<P: Piece, L: GameLogic<P>> Game<P, L> {
         }
     }
 
+    pub fn next_pieces(&self) -> &VecDeque<P> {
+        &self.data.next_pieces
+    }
     pub fn append_next_pieces(&mut self, pieces: &mut VecDeque<P>) {
         self.data.next_pieces.append(pieces)
     }
+    /// Insert `pieces` at the front of the next pieces, keeping the order.
+    pub fn prepend_next_pieces(&mut self, mut pieces: VecDeque<P>) {
+        pieces.append(&mut self.data.next_pieces);
+        self.data.next_pieces = pieces;
+    }
     pub fn set_next_pieces(&mut self, pieces: VecDeque<P>) {
         self.data.next_pieces = pieces;
     }
+    pub fn clear_next_pieces(&mut self) {
+        self.data.next_pieces.clear();
+    }
+    /// Drop `piece` from the top of the playfield at column `x` and lock it in any ongoing
+    /// state, e.g. to set up puzzles. Return the number of cleared rows.
+    ///
+    /// As with locking the falling piece, `GameLogic::after_lock()` is called, and the combo,
+    /// back-to-back, `num_cleared_lines` and events are updated. The game is over on the
+    /// top-out condition without placing the piece, and finished on reaching `line_goal`.
+    /// Unlike it, the line clear delay is skipped and T-Spins are never detected.
+    /// Fail if the game is over or in the error state, or if the piece can not be put or
+    /// overlaps the falling piece.
+    pub fn place(&mut self, piece: P, x: i32, rotation: Rotation) -> Result<usize, String>
+    where
+        P: fmt::Debug,
+    {
+        if self.is_game_over() || self.state_id() == GameStateId::Error {
+            return Err(format!("can not place in {:?} state", self.state_id()));
+        }
+        let playfield = &self.data.playfield;
+        let g = piece.grid(rotation);
+        let mut fp = FallingPiece {
+            piece,
+            x,
+            y: (playfield.grid.num_rows() - g.num_rows() + piece.grid_top_padding(rotation)) as i32,
+            rotation,
+        };
+        if !fp.can_put_onto(playfield) {
+            return Err(format!(
+                "can not place {:?} ({:?}) at x = {}",
+                piece, rotation, x
+            ));
+        }
+        fp.y -= fp.droppable_rows(playfield) as i32;
+        if let Some(falling_piece) = &self.data.falling_piece {
+            let mut pf = playfield.clone();
+            falling_piece.put_onto(&mut pf);
+            if !fp.can_put_onto(&pf) {
+                return Err(format!(
+                    "can not place {:?} ({:?}) at x = {} over the falling piece",
+                    piece, rotation, x
+                ));
+            }
+        }
+        let r = self.config.params.top_out_condition.check(&fp, playfield);
+        let r: Option<GameOverReason> = r.into();
+        if let Some(reason) = r {
+            self.handle_result(Ok(Some(Box::new(GameStateGameOver::new(reason)))));
+            return Err(format!("topped out: {:?}", reason));
+        }
+        fp.put_onto(&mut self.data.playfield);
+        self.config
+            .logic
+            .after_lock(&fp, &mut self.data.playfield, &mut self.data.rng);
+        let n = clear_filled_rows(&mut self.data, &self.config, TSpin::None);
+        if n == 0 {
+            self.data.combo = -1;
+        } else if is_line_goal_reached(&self.data, &self.config) {
+            self.handle_result(Ok(Some(Box::new(GameStateFinished))));
+        }
+        Ok(n)
+    }
+    /// Return a lightweight copy of the board and piece states for AI search.
+    /// Unlike `clone()`, the input manager, events and the state machine are not copied.
+    pub fn fork_for_search(&self) -> SearchGame<'_, P, L> {
+        SearchGame {
+            playfield: self.data.playfield.clone(),
+            falling_piece: self.data.falling_piece,
+            hold_piece: self.data.hold_piece,
+            next_pieces: self.data.next_pieces.clone(),
+            num_holds: self.data.num_holds_this_piece,
+            params: self.config.params,
+            logic: &self.config.logic,
+        }
+    }
+}
+
+//--- SearchGame
+
+/// Board and piece states forked from `Game` to simulate placements without frames.
+///
+/// Of `params`, only `hold_enabled`, `hold_limit` and `top_out_condition` are honored.
+/// Frame based ones like gravity and delays do not apply, held pieces are always spawned
+/// again, and line clears are not recorded.
+#[derive(Debug, Clone)]
+pub struct SearchGame<'a, P: Piece, L> {
+    pub playfield: Playfield<P>,
+    pub falling_piece: Option<FallingPiece<P>>,
+    pub hold_piece: Option<P>,
+    pub next_pieces: VecDeque<P>,
+    /// The number of holds for the current piece.
+    pub num_holds: u32,
+    pub params: GameParams,
+    pub logic: &'a L,
+}
+
+impl<'a, P: Piece, L: GameLogic<P>> SearchGame<'a, P, L> {
+    /// True if topped out or no piece can be spawned anymore.
+    pub fn is_game_over(&self) -> bool {
+        self.falling_piece.is_none()
+    }
+    pub fn move_piece(&mut self, dx: i32, dy: i32) -> bool {
+        let moved = self
+            .falling_piece
+            .and_then(|fp| fp.try_move(dx, dy, &self.playfield));
+        if moved.is_some() {
+            self.falling_piece = moved;
+        }
+        moved.is_some()
+    }
+    pub fn rotate(&mut self, cw: bool) -> bool {
+        let rotated = self
+            .falling_piece
+            .and_then(|fp| fp.try_rotate(cw, self.logic, &self.playfield));
+        if let Some(r) = rotated {
+            self.falling_piece = Some(r.falling_piece);
+        }
+        rotated.is_some()
+    }
+    pub fn hold(&mut self) -> bool {
+        let fp = match self.falling_piece {
+            Some(fp) if self.params.can_hold(self.num_holds) => fp,
+            _ => return false,
+        };
+        let piece = match self.hold_piece {
+            Some(p) => p,
+            None => match self.next_pieces.pop_front() {
+                Some(p) => p,
+                None => return false,
+            },
+        };
+        self.hold_piece = Some(fp.piece);
+        self.falling_piece = self
+            .logic
+            .spawn_piece(piece, &self.playfield)
+            .falling_piece();
+        self.num_holds += 1;
+        true
+    }
+    /// Drop and lock the falling piece, then spawn the next one.
+    /// Return the number of cleared rows, or None if there is no falling piece.
+    /// On the top-out condition, the piece is not locked and the game is over.
+    pub fn hard_drop(&mut self) -> Option<usize> {
+        let fp = self.falling_piece?;
+        let dropped = FallingPiece {
+            y: fp.y - fp.droppable_rows(&self.playfield) as i32,
+            ..fp
+        };
+        let r = self
+            .params
+            .top_out_condition
+            .check(&dropped, &self.playfield);
+        if !r.is_empty() {
+            self.falling_piece = None;
+            return None;
+        }
+        let n = self.playfield.lock_piece(&dropped).ok()?;
+        self.falling_piece = self
+            .next_pieces
+            .pop_front()
+            .and_then(|p| self.logic.spawn_piece(p, &self.playfield).falling_piece());
+        self.num_holds = 0;
+        Some(n)
+    }
+}
+
+//--- GameBuilder
+
+/// Build `Game` from an arbitrary board state. Useful for tests and puzzles.
+#[derive(Debug, Clone)]
+pub struct GameBuilder<P: Piece, L> {
+    config: GameConfig<L>,
+    playfield: Playfield<P>,
+    falling_piece: Option<FallingPiece<P>>,
+    hold_piece: Option<P>,
+    next_pieces: VecDeque<P>,
+    seed: Option<u64>,
+}
+
+impl<P: Piece, L: GameLogic<P>> GameBuilder<P, L> {
+    pub fn new(config: GameConfig<L>, playfield: Playfield<P>) -> Self {
+        Self {
+            config,
+            playfield,
+            falling_piece: None,
+            hold_piece: None,
+            next_pieces: VecDeque::new(),
+            seed: None,
+        }
+    }
+    pub fn falling_piece(mut self, falling_piece: FallingPiece<P>) -> Self {
+        self.falling_piece = Some(falling_piece);
+        self
+    }
+    pub fn hold_piece(mut self, piece: P) -> Self {
+        self.hold_piece = Some(piece);
+        self
+    }
+    pub fn next_pieces(mut self, pieces: VecDeque<P>) -> Self {
+        self.next_pieces = pieces;
+        self
+    }
+    /// Record the seed the next pieces were generated with, cf. `Game::seed()`.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+    /// Build `Game` and advance it to `GameStateId::Play`.
+    pub fn build(self) -> Result<Game<P, L>, String> {
+        let max_frames = self.config.params.are as usize + 2;
+        let mut data = GameData::new(
+            self.playfield,
+            self.falling_piece,
+            self.hold_piece,
+            self.next_pieces,
+            &self.config.params,
+        );
+        data.seed = self.seed;
+        let mut game = Game::new(self.config, data);
+        if !game.update_until(GameStateId::Play, max_frames) {
+            return Err(format!("failed to enter play state: {:?}", game.state_id()));
+        }
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::tetro::{Piece as TetroPiece, PieceGrid, WorldRuleLogic};
+
+    fn new_game(
+        grid: PieceGrid,
+        falling_piece: Option<FallingPiece<TetroPiece>>,
+        next_pieces: VecDeque<TetroPiece>,
+    ) -> Game<TetroPiece, WorldRuleLogic> {
+        new_game_with_params(GameParams::default(), grid, falling_piece, next_pieces)
+    }
+
+    fn new_game_with_params(
+        params: GameParams,
+        grid: PieceGrid,
+        falling_piece: Option<FallingPiece<TetroPiece>>,
+        next_pieces: VecDeque<TetroPiece>,
+    ) -> Game<TetroPiece, WorldRuleLogic> {
+        let config = GameConfig {
+            params,
+            logic: WorldRuleLogic::default(),
+        };
+        let data = GameData::new(
+            Playfield {
+                visible_rows: 20,
+                grid,
+            },
+            falling_piece,
+            None,
+            next_pieces,
+            &config.params,
+        );
+        Game::new(config, data)
+    }
+
+    /// A 10x40 playfield with garbage at the non-'.' cells of `rows`, given from the top.
+    /// Shared with the tests of other modules.
+    pub(crate) fn playfield_from_rows(rows: &[&str]) -> Playfield<TetroPiece> {
+        let mut grid = PieceGrid::new(10, 40, vec![]);
+        for (i, row) in rows.iter().enumerate() {
+            let y = rows.len() - 1 - i;
+            for (x, c) in row.chars().enumerate() {
+                if c != '.' {
+                    grid.set_cell(x, y, Cell::Garbage);
+                }
+            }
+        }
+        Playfield {
+            visible_rows: 20,
+            grid,
+        }
+    }
+
+    /// The grid whose row 0 is filled except columns 3..7, cf. `line_clear_piece()`.
+    fn line_clear_grid() -> PieceGrid {
+        playfield_from_rows(&["xxx....xxx"]).grid
+    }
+
+    /// The I piece clearing row 0 of `line_clear_grid()` by a hard drop.
+    fn line_clear_piece() -> FallingPiece<TetroPiece> {
+        FallingPiece {
+            piece: TetroPiece::I,
+            x: 2,
+            y: 0,
+            rotation: Rotation::default(),
+        }
+    }
+
+    /// A game with `line_clear_grid()`, `line_clear_piece()` falling and T next.
+    fn new_line_clear_game(params: GameParams) -> Game<TetroPiece, WorldRuleLogic> {
+        new_game_with_params(
+            params,
+            line_clear_grid(),
+            Some(line_clear_piece()),
+            vec![TetroPiece::T].into(),
+        )
+    }
+
+    fn blocked_spawn_grid() -> PieceGrid {
+        let mut grid = PieceGrid::new(10, 40, vec![]);
+        for y in 15..25 {
+            for x in 1..10 {
+                grid.set_cell(x, y, Cell::Garbage);
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn display_cell() {
+        let fp = FallingPiece {
+            piece: TetroPiece::O,
+            x: 3,
+            y: 10,
+            rotation: Rotation::default(),
+        };
+        let mut grid = PieceGrid::new(10, 40, vec![]);
+        grid.set_cell(0, 0, Cell::Garbage);
+        let game = new_game(grid, Some(fp), VecDeque::new());
+        let data = game.data();
+        let params = &game.config().params;
+        let n = fp.droppable_rows(&data.playfield);
+        let g = fp.grid();
+        for gy in 0..g.num_rows() {
+            for gx in 0..g.num_cols() {
+                if !matches!(g.cell(gx, gy), Cell::Block(_)) {
+                    continue;
+                }
+                let x = fp.x as usize + gx;
+                let y = fp.y as usize + gy;
+                assert!(matches!(
+                    data.display_cell(x, y, params),
+                    Cell::Block(TetroPiece::O)
+                ));
+                assert!(matches!(
+                    data.display_cell(x, y - n, params),
+                    Cell::Ghost(TetroPiece::O)
+                ));
+            }
+        }
+        assert!(matches!(data.display_cell(0, 0, params), Cell::Garbage));
+        assert!(matches!(data.display_cell(9, 0, params), Cell::Empty));
+    }
+
+    #[test]
+    fn ghost_piece_disabled() {
+        let fp = FallingPiece {
+            piece: TetroPiece::O,
+            x: 3,
+            y: 10,
+            rotation: Rotation::default(),
+        };
+        let grid = PieceGrid::new(10, 40, vec![]);
+        let game = new_game(grid.clone(), Some(fp), VecDeque::new());
+        let gp = game.data().ghost_piece(&game.config().params).unwrap();
+        assert_eq!(0, gp.droppable_rows(&game.data().playfield));
+        assert!(gp.can_put_onto(&game.data().playfield));
+
+        let params = GameParams {
+            ghost_piece_enabled: false,
+            ..GameParams::default()
+        };
+        let game = new_game_with_params(params, grid, Some(fp), VecDeque::new());
+        let data = game.data();
+        let params = &game.config().params;
+        assert!(fp.droppable_rows(&data.playfield) > 0);
+        assert!(data.ghost_piece(params).is_none());
+        assert!((0..10).all(|x| matches!(data.display_cell(x, 0, params), Cell::Empty)));
+    }
+
+    #[test]
+    fn firm_drop_disabled() {
+        let fp = FallingPiece {
+            piece: TetroPiece::O,
+            x: 3,
+            y: 10,
+            rotation: Rotation::default(),
+        };
+        let params = GameParams {
+            gravity: 0.0,
+            ..GameParams::default()
+        };
+        let grid = PieceGrid::new(10, 40, vec![]);
+        let mut game = new_game_with_params(params, grid.clone(), Some(fp), VecDeque::new());
+        game.update(Input::default());
+        game.update(Input::FIRM_DROP);
+        assert_eq!(
+            0,
+            game.data()
+                .falling_piece
+                .unwrap()
+                .droppable_rows(&game.data().playfield)
+        );
+        assert!(game
+            .data()
+            .events
+            .iter()
+            .any(|e| matches!(e, GameEvent::SoftDropped(n) if *n > 0)));
+
+        let params = GameParams {
+            gravity: 0.0,
+            firm_drop_enabled: false,
+            ..GameParams::default()
+        };
+        let mut game = new_game_with_params(params, grid, Some(fp), VecDeque::new());
+        game.update(Input::default());
+        game.update(Input::FIRM_DROP);
+        assert_eq!(fp.y, game.data().falling_piece.unwrap().y);
+        assert!(!game
+            .data()
+            .events
+            .iter()
+            .any(|e| matches!(e, GameEvent::SoftDropped(_))));
+
+        // soft drop
+        game.update(Input::SOFT_DROP);
+        assert_eq!(fp.y - 1, game.data().falling_piece.unwrap().y);
+        assert!(game
+            .data()
+            .events
+            .iter()
+            .any(|e| matches!(e, GameEvent::SoftDropped(1))));
+    }
+
+    #[test]
+    fn line_clear_are() {
+        fn frames_to_play(are: Frames, line_clear_are: Option<Frames>) -> Frames {
+            let params = GameParams {
+                are,
+                line_clear_are,
+                line_clear_delay: 0.into(),
+                ..GameParams::default()
+            };
+            let mut game = new_line_clear_game(params);
+            game.update(Input::default());
+            game.update(Input::HARD_DROP);
+            assert!(game.update_until(GameStateId::SpawnPiece, 10));
+            let started_at = game.frame_num();
+            assert!(game.update_until(GameStateId::Play, 100));
+            game.frame_num() - started_at
+        }
+        assert_eq!(frames_to_play(5, Some(5)), frames_to_play(10, Some(5)));
+        assert_eq!(frames_to_play(5, Some(5)) + 5, frames_to_play(5, Some(10)));
+        assert_eq!(frames_to_play(5, Some(5)), frames_to_play(5, None));
+    }
+
+    #[test]
+    fn fork_for_search() {
+        let fp = FallingPiece {
+            y: 10,
+            ..line_clear_piece()
+        };
+        let mut game = new_game(
+            line_clear_grid(),
+            Some(fp),
+            vec![TetroPiece::T, TetroPiece::O].into(),
+        );
+        game.update(Input::default());
+        let playfield = game.data().playfield.clone();
+
+        let mut search = game.fork_for_search();
+        assert!(!search.move_piece(0, -30));
+        assert!(search.hold());
+        assert!(!search.hold());
+        assert_eq!(TetroPiece::I, search.hold_piece.unwrap());
+        assert_eq!(TetroPiece::T, search.falling_piece.unwrap().piece);
+        assert!(search.rotate(true));
+        assert_eq!(Some(0), search.hard_drop());
+        assert_eq!(TetroPiece::O, search.falling_piece.unwrap().piece);
+        assert_eq!(0, search.num_holds);
+        assert!(search.hold());
+        assert_eq!(TetroPiece::I, search.falling_piece.unwrap().piece);
+        assert!(search.move_piece(0, -1));
+
+        // The original game is not changed.
+        assert!(game
+            .data()
+            .playfield
+            .grid
+            .equals_by(&playfield.grid, Cell::eq_ignoring_ghost));
+        assert!(game.data().hold_piece.is_none());
+        assert_eq!(2, game.data().next_pieces.len());
+
+        let mut search = game.fork_for_search();
+        assert_eq!(Some(1), search.hard_drop());
+        assert!(search.playfield.is_empty());
+        assert_eq!(Some(0), search.hard_drop());
+        assert_eq!(Some(0), search.hard_drop());
+        assert_eq!(None, search.hard_drop());
+        assert!(search.is_game_over());
+    }
+
+    #[test]
+    fn search_game_params() {
+        let next = || vec![TetroPiece::T, TetroPiece::O, TetroPiece::I, TetroPiece::S].into();
+        let params = GameParams {
+            hold_limit: Some(2),
+            ..GameParams::default()
+        };
+        let grid = PieceGrid::new(10, 40, vec![]);
+        let mut game = new_game_with_params(params, grid.clone(), None, next());
+        assert!(game.update_until(GameStateId::Play, 100));
+        let mut search = game.fork_for_search();
+        assert!(search.hold());
+        assert!(search.hold());
+        assert!(!search.hold());
+
+        let params = GameParams {
+            hold_enabled: false,
+            ..GameParams::default()
+        };
+        let mut game = new_game_with_params(params, grid, None, next());
+        assert!(game.update_until(GameStateId::Play, 100));
+        assert!(!game.fork_for_search().hold());
+
+        // Locked out above the visible rows.
+        let mut grid = PieceGrid::new(10, 40, vec![]);
+        for y in 0..21 {
+            grid.set_cell(0, y, Cell::Garbage);
+        }
+        let fp = FallingPiece {
+            piece: TetroPiece::I,
+            x: -2,
+            y: 30,
+            rotation: Rotation::Cw90,
+        };
+        let game = new_game(grid, Some(fp), next());
+        let mut search = game.fork_for_search();
+        assert_eq!(None, search.hard_drop());
+        assert!(search.is_game_over());
+        assert_eq!(21, search.playfield.grid.count_filled_in(0..10, 0..40));
+    }
+
+    #[test]
+    fn cell_piece() {
+        let cells: [Cell<TetroPiece>; 4] = [
+            Cell::Empty,
+            Cell::Block(TetroPiece::T),
+            Cell::Ghost(TetroPiece::I),
+            Cell::Garbage,
+        ];
+        let pieces: Vec<_> = cells.iter().map(|c| c.piece()).collect();
+        assert_eq!(
+            vec![None, Some(TetroPiece::T), Some(TetroPiece::I), None],
+            pieces
+        );
+        let blocks: Vec<_> = cells.iter().map(|c| c.is_block()).collect();
+        assert_eq!(vec![false, true, false, false], blocks);
+    }
+
+    #[test]
+    fn eq_ignoring_ghost() {
+        let mut grid = PieceGrid::new(10, 40, vec![]);
+        grid.set_cell(0, 0, Cell::Garbage);
+        grid.set_cell(1, 0, Cell::Block(TetroPiece::T));
+        let mut with_ghosts = grid.clone();
+        with_ghosts.set_cell(5, 0, Cell::Ghost(TetroPiece::I));
+        with_ghosts.set_cell(6, 0, Cell::Ghost(TetroPiece::I));
+        assert!(grid.equals_by(&with_ghosts, Cell::eq_ignoring_ghost));
+        with_ghosts.set_cell(1, 0, Cell::Block(TetroPiece::S));
+        assert!(!grid.equals_by(&with_ghosts, Cell::eq_ignoring_ghost));
+    }
+
+    #[test]
+    fn input_mapper() {
+        let mut mapper = InputMapper::default();
+        mapper.map(Input::HOLD, Input::MOVE_LEFT | Input::ROTATE_CW);
+        assert_eq!(
+            Input::MOVE_LEFT | Input::ROTATE_CW | Input::HARD_DROP,
+            mapper.apply(Input::HOLD | Input::HARD_DROP)
+        );
+        assert_eq!(Input::MOVE_RIGHT, mapper.apply(Input::MOVE_RIGHT));
+
+        let fp = FallingPiece {
+            piece: TetroPiece::T,
+            x: 3,
+            y: 10,
+            rotation: Rotation::default(),
+        };
+        let grid = PieceGrid::new(10, 40, vec![]);
+        let mut game = new_game(grid, Some(fp), vec![TetroPiece::O].into());
+        game.set_input_mapper(mapper);
+        game.update(Input::default());
+        game.update(Input::HOLD);
+        let moved = game.data().falling_piece.unwrap();
+        assert_eq!(TetroPiece::T, moved.piece);
+        assert_eq!(2, moved.x);
+        assert_eq!(Rotation::Cw90 as usize, moved.rotation as usize);
+        assert!(game.data().hold_piece.is_none());
+    }
+
+    #[test]
+    fn visible_grid() {
+        let mut grid = PieceGrid::new(10, 40, vec![]);
+        grid.set_cell(0, 0, Cell::Garbage);
+        grid.set_cell(9, 19, Cell::Block(TetroPiece::T));
+        grid.set_cell(5, 20, Cell::Garbage);
+        let playfield = Playfield {
+            visible_rows: 20,
+            grid,
+        };
+        let visible = playfield.visible_grid();
+        assert_eq!((10, 20), (visible.num_cols(), visible.num_rows()));
+        assert!(visible.equals_by(&playfield.grid.crop(0..10, 0..20), Cell::eq_ignoring_ghost));
+        assert!(matches!(visible.cell(0, 0), Cell::Garbage));
+        assert!(matches!(visible.cell(9, 19), Cell::Block(TetroPiece::T)));
+        assert_eq!(2, visible.count_filled_in(0..10, 0..20));
+    }
+
+    #[test]
+    fn fill_cheese() {
+        let mut grid = PieceGrid::new(10, 40, vec![]);
+        grid.set_cell(4, 0, Cell::Block(TetroPiece::T));
+        let mut playfield = Playfield {
+            visible_rows: 20,
+            grid,
+        };
+        let mut other = playfield.clone();
+        assert!(playfield.fill_cheese(8, &mut GameRng::new(1)).is_ok());
+        let grid = &playfield.grid;
+        let holes: Vec<_> = (0..8)
+            .map(|y| {
+                assert_eq!(9, grid.count_filled_in(0..10, y..y + 1));
+                (0..10)
+                    .position(|x| matches!(grid.cell(x, y), Cell::Empty))
+                    .unwrap()
+            })
+            .collect();
+        assert!(holes.windows(2).all(|w| w[0] != w[1]));
+        assert!(matches!(grid.cell(4, 8), Cell::Block(TetroPiece::T)));
+        assert_eq!(73, grid.count_filled_in(0..10, 0..40));
+
+        // Deterministic by the seed.
+        assert!(other.fill_cheese(8, &mut GameRng::new(1)).is_ok());
+        assert!(other
+            .grid
+            .equals_by(&playfield.grid, Cell::eq_ignoring_ghost));
+
+        // The T block would be pushed out of the grid.
+        assert!(other.fill_cheese(32, &mut GameRng::new(1)).is_err());
+        assert!(other
+            .grid
+            .equals_by(&playfield.grid, Cell::eq_ignoring_ghost));
+        assert!(other.fill_cheese(41, &mut GameRng::new(1)).is_err());
+    }
+
+    #[test]
+    fn board_string() {
+        let mut board = String::new();
+        for _ in 0..36 {
+            board.push_str("..........\n");
+        }
+        board.push_str("....T.....\n");
+        board.push_str("...TTT....\n");
+        board.push_str("x.IIII...x\n");
+        board.push_str("xxxxxxxxx.\n");
+        let data =
+            GameData::from_board_string(&board, vec![TetroPiece::O].into(), Some(TetroPiece::S))
+                .unwrap();
+        assert_eq!(
+            (10, 40),
+            (
+                data.playfield.grid.num_cols(),
+                data.playfield.grid.num_rows()
+            )
+        );
+        assert_eq!(20, data.playfield.visible_rows);
+        assert!(matches!(
+            data.playfield.grid.cell(4, 3),
+            Cell::Block(TetroPiece::T)
+        ));
+        assert!(matches!(data.playfield.grid.cell(9, 1), Cell::Garbage));
+        assert_eq!(Some(TetroPiece::S), data.hold_piece);
+        assert_eq!(board, data.to_board_string());
+
+        // The falling piece is not included.
+        let mut game = new_game(
+            data.playfield.grid.clone(),
+            None,
+            vec![TetroPiece::O].into(),
+        );
+        assert!(game.update_until(GameStateId::Play, 100));
+        assert_eq!(board, game.data().to_board_string());
+
+        assert!(
+            GameData::<TetroPiece>::from_board_string("..\n...\n", VecDeque::new(), None).is_err()
+        );
+        assert!(
+            GameData::<TetroPiece>::from_board_string("..\n.?\n", VecDeque::new(), None).is_err()
+        );
+    }
+
+    #[test]
+    fn occupancy_mask() {
+        let mut grid = PieceGrid::new(3, 2, vec![]);
+        grid.set_cell(0, 0, Cell::Garbage);
+        grid.set_cell(1, 0, Cell::Block(TetroPiece::T));
+        grid.set_cell(2, 1, Cell::Ghost(TetroPiece::T));
+        let mask = grid.map_cells(|c| matches!(c, Cell::Block(_) | Cell::Garbage));
+        let expected =
+            grid::Grid::from_rows(vec![vec![false, false, false], vec![true, true, false]]);
+        assert_eq!(expected, mask);
+    }
+
+    #[test]
+    fn line_clear_gravity() {
+        fn board_after_clear(line_clear_gravity: LineClearGravity) -> PieceGrid {
+            let params = GameParams {
+                line_clear_gravity,
+                line_clear_delay: 0.into(),
+                ..GameParams::default()
+            };
+            let mut grid = line_clear_grid();
+            // A floating block over a gap above the cleared line.
+            grid.set_cell(0, 1, Cell::Garbage);
+            grid.set_cell(1, 2, Cell::Garbage);
+            let fp = line_clear_piece();
+            let mut game = new_game_with_params(params, grid, Some(fp), vec![TetroPiece::T].into());
+            game.update(Input::default());
+            game.update(Input::HARD_DROP);
+            game.update_until(GameStateId::SpawnPiece, 10);
+            game.data().playfield.grid.clone()
+        }
+        let naive = board_after_clear(LineClearGravity::Naive);
+        assert!(matches!(naive.cell(0, 0), Cell::Garbage));
+        assert!(matches!(naive.cell(1, 0), Cell::Empty));
+        assert!(matches!(naive.cell(1, 1), Cell::Garbage));
+        let cascade = board_after_clear(LineClearGravity::Cascade);
+        assert!(matches!(cascade.cell(0, 0), Cell::Garbage));
+        assert!(matches!(cascade.cell(1, 0), Cell::Garbage));
+        assert!(matches!(cascade.cell(1, 1), Cell::Empty));
+    }
+
+    #[test]
+    fn line_clear_progress() {
+        let params = GameParams {
+            line_clear_delay: 5.into(),
+            ..GameParams::default()
+        };
+        let mut game = new_line_clear_game(params);
+        game.update(Input::default());
+        game.update(Input::HARD_DROP);
+        let progress: Vec<_> = game
+            .fast_forward()
+            .into_iter()
+            .filter_map(|e| match e {
+                GameEvent::LineClearProgress { rows, frame, total } => Some((rows, frame, total)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(5, progress.len());
+        for (i, (rows, frame, total)) in progress.into_iter().enumerate() {
+            assert_eq!(vec![0], rows);
+            assert_eq!(i as Frames + 1, frame);
+            assert_eq!(5, total);
+        }
+    }
+
+    #[test]
+    fn line_goal() {
+        let params = GameParams {
+            line_goal: Some(2),
+            ..GameParams::default()
+        };
+        let grid = playfield_from_rows(&["xxx....xxx", "xxx....xxx"]).grid;
+        let fp = line_clear_piece();
+        let next = vec![TetroPiece::I, TetroPiece::T].into();
+        let mut game = new_game_with_params(params, grid, Some(fp), next);
+        game.update(Input::default());
+        game.update(Input::HARD_DROP);
+        let events = game.fast_forward();
+        assert!(!events
+            .iter()
+            .any(|e| matches!(e, GameEvent::GoalReached { .. })));
+        assert_eq!(GameStateId::Play, game.state_id());
+        assert_eq!(1, game.data().num_cleared_lines);
+        // The spawned I piece drops onto the remaining hole.
+        game.update(Input::HARD_DROP);
+        let events = game.fast_forward();
+        assert_eq!(GameStateId::Finished, game.state_id());
+        assert!(game.is_game_over());
+        assert!(game.game_over_reason().is_none());
+        assert_eq!(2, game.data().num_cleared_lines);
+        let frames = game.frame_num();
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, GameEvent::GoalReached { frames: f } if *f == frames)));
+        game.update(Input::default());
+        assert_eq!(frames, game.data().elapsed_frames);
+    }
+
+    #[test]
+    fn bounding_rect() {
+        let fp = FallingPiece {
+            piece: TetroPiece::I,
+            x: 2,
+            y: 5,
+            rotation: Rotation::Cw0,
+        };
+        assert_eq!((3..7, 7..8), fp.bounding_rect());
+        let fp = FallingPiece {
+            rotation: Rotation::Cw90,
+            ..fp
+        };
+        assert_eq!((4..5, 5..9), fp.bounding_rect());
+    }
+
+    #[test]
+    fn place() {
+        let grid = PieceGrid::new(10, 40, vec![]);
+        let mut game = new_game(grid, None, vec![TetroPiece::T].into());
+        // The I piece grid has a left padding.
+        assert_eq!(Ok(0), game.place(TetroPiece::I, -1, Rotation::Cw0));
+        assert_eq!(Ok(0), game.place(TetroPiece::I, 3, Rotation::Cw0));
+        let grid = &game.data().playfield.grid;
+        assert_eq!(8, grid.count_filled_in(0..10, 0..1));
+        assert!(matches!(grid.cell(0, 0), Cell::Block(TetroPiece::I)));
+        // The O piece occupies columns 8-9.
+        assert_eq!(Ok(1), game.place(TetroPiece::O, 7, Rotation::Cw0));
+        let grid = &game.data().playfield.grid;
+        assert_eq!(2, grid.count_filled_in(0..10, 0..40));
+        assert!(matches!(grid.cell(8, 0), Cell::Block(TetroPiece::O)));
+        assert!(game.place(TetroPiece::I, 8, Rotation::Cw0).is_err());
+        let data = game.data();
+        assert_eq!((1, 0), (data.num_cleared_lines, data.combo));
+        assert!(data
+            .events
+            .iter()
+            .any(|e| matches!(e, GameEvent::LineCleared(1, TSpin::None))));
+        assert_eq!(Ok(0), game.place(TetroPiece::O, 0, Rotation::Cw0));
+        assert_eq!(-1, game.data().combo);
+    }
+
+    #[test]
+    fn place_over_falling_piece() {
+        let fp = FallingPiece {
+            piece: TetroPiece::O,
+            x: 3,
+            y: 0,
+            rotation: Rotation::Cw0,
+        };
+        let grid = PieceGrid::new(10, 40, vec![]);
+        let mut game = new_game(grid, Some(fp), VecDeque::new());
+        // The O piece occupies columns 4-5.
+        assert!(game.place(TetroPiece::O, 2, Rotation::Cw0).is_err());
+        assert!(game.data().playfield.grid.iter_filled().next().is_none());
+        assert_eq!(Ok(0), game.place(TetroPiece::O, 5, Rotation::Cw0));
+    }
+
+    #[test]
+    fn time_limit() {
+        let params = GameParams {
+            gravity: 0.0,
+            time_limit: Some(10),
+            ..GameParams::default()
+        };
+        let grid = PieceGrid::new(10, 40, vec![]);
+        let mut game = new_game_with_params(params, grid, None, vec![TetroPiece::T].into());
+        game.set_transition_validator(Some(TransitionValidator::standard()));
+        game.step_n(Input::default(), 9);
+        assert!(!game.is_game_over());
+        game.update(Input::default());
+        assert_eq!(GameStateId::Finished, game.state_id());
+        assert!(game
+            .data()
+            .events
+            .iter()
+            .any(|e| matches!(e, GameEvent::GoalReached { frames: 10 })));
+        game.update(Input::default());
+        assert_eq!(10, game.data().elapsed_frames);
+
+        // Keep the error on the last frame, caused by no next pieces.
+        let params = GameParams {
+            time_limit: Some(2),
+            ..params
+        };
+        let grid = PieceGrid::new(10, 40, vec![]);
+        let mut game = new_game_with_params(params, grid, None, VecDeque::new());
+        game.step_n(Input::default(), 2);
+        assert_eq!(GameStateId::Error, game.state_id());
+    }
+
+    #[test]
+    fn place_line_goal() {
+        let params = GameParams {
+            line_goal: Some(1),
+            ..GameParams::default()
+        };
+        let mut grid = PieceGrid::new(10, 40, vec![]);
+        for x in 0..8 {
+            grid.set_cell(x, 0, Cell::Garbage);
+            grid.set_cell(x, 1, Cell::Garbage);
+        }
+        let mut game = new_game_with_params(params, grid, None, vec![TetroPiece::T].into());
+        assert_eq!(Ok(2), game.place(TetroPiece::O, 7, Rotation::Cw0));
+        assert_eq!(GameStateId::Finished, game.state_id());
+        assert!(game
+            .data()
+            .events
+            .iter()
+            .any(|e| matches!(e, GameEvent::GoalReached { .. })));
+    }
+
+    #[test]
+    fn place_after_game_end() {
+        let params = GameParams {
+            line_goal: Some(1),
+            ..GameParams::default()
+        };
+        let mut grid = PieceGrid::new(10, 40, vec![]);
+        for x in 0..8 {
+            grid.set_cell(x, 0, Cell::Garbage);
+        }
+        let mut game = new_game_with_params(params, grid, None, vec![TetroPiece::T].into());
+        assert_eq!(Ok(1), game.place(TetroPiece::O, 7, Rotation::Cw0));
+        let data = game.data().clone();
+        assert!(game.place(TetroPiece::O, 0, Rotation::Cw0).is_err());
+        let grid = &game.data().playfield.grid;
+        assert_eq!(2, grid.count_filled_in(0..10, 0..40));
+        assert!(matches!(grid.cell(0, 0), Cell::Empty));
+        assert_eq!(data.combo, game.data().combo);
+        assert_eq!(data.events.len(), game.data().events.len());
+
+        let grid = PieceGrid::new(10, 40, vec![]);
+        let mut game = new_game(grid, None, vec![TetroPiece::T].into());
+        game.set_transition_validator(Some(TransitionValidator::default()));
+        game.update(Input::default());
+        assert_eq!(GameStateId::Error, game.state_id());
+        assert!(game.place(TetroPiece::O, 0, Rotation::Cw0).is_err());
+        assert!(game.data().playfield.grid.iter_filled().next().is_none());
+    }
+
+    #[test]
+    fn slide_distance() {
+        let mut playfield = Playfield {
+            visible_rows: 20,
+            grid: PieceGrid::new(10, 40, vec![]),
+        };
+        let fp = FallingPiece {
+            piece: TetroPiece::T,
+            x: 4,
+            y: 5,
+            rotation: Rotation::Cw0,
+        };
+        // The T piece occupies columns 4-6.
+        assert_eq!(4, fp.slide_distance(&playfield, Side::Left));
+        assert_eq!(3, fp.slide_distance(&playfield, Side::Right));
+        playfield.grid.set_cell(1, 6, Cell::Garbage);
+        assert_eq!(2, fp.slide_distance(&playfield, Side::Left));
+        assert!(fp.try_move(-2, 0, &playfield).is_some());
+        assert!(fp.try_move(-3, 0, &playfield).is_none());
+    }
+
+    #[test]
+    fn gravity_before_input() {
+        fn y_after_moving_off_ledge(gravity_before_input: bool) -> i32 {
+            let params = GameParams {
+                gravity: 20.0,
+                gravity_before_input,
+                ..GameParams::default()
+            };
+            // O piece above a one-column ledge at x=3, which is 2 rows high.
+            let mut grid = PieceGrid::new(10, 40, vec![]);
+            grid.set_cell(3, 0, Cell::Garbage);
+            grid.set_cell(3, 1, Cell::Garbage);
+            let fp = FallingPiece {
+                piece: TetroPiece::O,
+                x: 2,
+                y: 3,
+                rotation: Rotation::default(),
+            };
+            let mut game = new_game_with_params(params, grid, Some(fp), VecDeque::new());
+            game.update(Input::default());
+            assert_eq!(3, game.data().falling_piece.unwrap().y);
+            game.update(Input::MOVE_RIGHT);
+            let fp = game.data().falling_piece.unwrap();
+            assert_eq!(3, fp.x);
+            fp.y
+        }
+        // Moved off the ledge first, then fell to the floor.
+        assert_eq!(-1, y_after_moving_off_ledge(false));
+        // Fell onto the ledge first, then moved off it.
+        assert_eq!(1, y_after_moving_off_ledge(true));
+    }
+
+    #[test]
+    fn lock_flash_delay() {
+        for &delay in &[0, 1, 5] {
+            let params = GameParams {
+                lock_flash_delay: delay,
+                ..GameParams::default()
+            };
+            let fp = FallingPiece {
+                piece: TetroPiece::O,
+                x: 3,
+                y: 0,
+                rotation: Rotation::default(),
+            };
+            let grid = PieceGrid::new(10, 40, vec![]);
+            let mut game = new_game_with_params(params, grid, Some(fp), vec![TetroPiece::T].into());
+            game.update(Input::default());
+            game.update(Input::HARD_DROP);
+            assert_eq!(GameStateId::Lock, game.state_id());
+            game.update(Input::default());
+            // The piece is placed on the first frame.
+            assert_eq!(4, game.data().playfield.grid.count_filled_in(0..10, 0..40));
+            for _ in 0..delay {
+                assert_eq!(GameStateId::Lock, game.state_id());
+                game.update(Input::default());
+            }
+            assert_eq!(GameStateId::SpawnPiece, game.state_id());
+        }
+    }
+
+    #[test]
+    fn state_snapshot() {
+        let params = GameParams {
+            line_clear_delay: 3.into(),
+            ..GameParams::default()
+        };
+        let mut game = new_line_clear_game(params);
+        assert_eq!(StateSnapshot::Init, game.state_snapshot());
+        game.update(Input::default());
+        assert_eq!(
+            StateSnapshot::Play {
+                lock_delay_remaining: params.lock_delay
+            },
+            game.state_snapshot()
+        );
+        game.update(Input::HARD_DROP);
+        assert_eq!(StateSnapshot::Lock, game.state_snapshot());
+        game.update(Input::default());
+        assert_eq!(GameStateId::LineClear, game.state_id());
+        let mut remainings = Vec::new();
+        while game.state_id() == GameStateId::LineClear {
+            game.update(Input::default());
+            if let StateSnapshot::LineClear { remaining_frames } = game.state_snapshot() {
+                remainings.push(remaining_frames);
+            }
+        }
+        assert_eq!(vec![2, 1, 0], remainings);
+        assert_eq!(
+            StateSnapshot::SpawnPiece {
+                remaining_frames: params.are
+            },
+            game.state_snapshot()
+        );
+    }
+
+    #[test]
+    fn resolve_conflicts() {
+        let input = Input::MOVE_LEFT | Input::MOVE_RIGHT | Input::ROTATE_CW | Input::ROTATE_CCW;
+        assert_eq!(
+            Input::MOVE_LEFT | Input::ROTATE_CW,
+            input.resolve_conflicts(ConflictPolicy::PreferLeft)
+        );
+        assert_eq!(
+            Input::MOVE_RIGHT | Input::ROTATE_CCW,
+            input.resolve_conflicts(ConflictPolicy::PreferRight)
+        );
+        assert_eq!(
+            Input::HARD_DROP,
+            (input | Input::HARD_DROP).resolve_conflicts(ConflictPolicy::Cancel)
+        );
+        // No conflicts
+        let input = Input::MOVE_RIGHT | Input::ROTATE_CW;
+        for &policy in &[
+            ConflictPolicy::PreferLeft,
+            ConflictPolicy::PreferRight,
+            ConflictPolicy::Cancel,
+        ] {
+            assert_eq!(input, input.resolve_conflicts(policy));
+        }
+    }
+
+    #[test]
+    fn input_conflict_policy() {
+        let params = GameParams {
+            input_conflict_policy: Some(ConflictPolicy::PreferRight),
+            ..GameParams::default()
+        };
+        let grid = PieceGrid::new(10, 40, vec![]);
+        let mut game = new_game_with_params(params, grid, None, vec![TetroPiece::T].into());
+        assert!(game.update_until(GameStateId::Play, 100));
+        let x = game.data().falling_piece.unwrap().x;
+        game.update(Input::MOVE_LEFT | Input::MOVE_RIGHT);
+        assert_eq!(x + 1, game.data().falling_piece.unwrap().x);
+    }
+
+    #[test]
+    fn input_token() {
+        for input in INPUTS.iter() {
+            assert_eq!(Ok(*input), Input::from_token(&input.to_token()));
+        }
+        assert_eq!("HD", Input::HARD_DROP.to_token());
+        assert_eq!("ML|RCW", (Input::MOVE_LEFT | Input::ROTATE_CW).to_token());
+        for input in &[
+            Input::empty(),
+            Input::MOVE_LEFT | Input::ROTATE_CW,
+            Input::SOFT_DROP | Input::MOVE_RIGHT | Input::ROTATE_CCW | Input::HOLD,
+        ] {
+            assert_eq!(Ok(*input), Input::from_token(&input.to_token()));
+        }
+        assert!(Input::from_token("HD|X").is_err());
+    }
+
+    #[test]
+    fn playfield_lock_piece() {
+        let mut grid = line_clear_grid();
+        grid.set_cell(0, 1, Cell::Garbage);
+        let mut playfield = Playfield {
+            visible_rows: 20,
+            grid,
+        };
+        let fp = FallingPiece {
+            y: -2,
+            ..line_clear_piece()
+        };
+        assert_eq!(Ok(1), playfield.lock_piece(&fp));
+        assert!(playfield.grid.cell(0, 0).is_garbage());
+        assert!(!playfield.grid.cell(1, 0).is_garbage());
+
+        let fp = FallingPiece {
+            piece: TetroPiece::I,
+            x: -1,
+            y: -2,
+            rotation: Rotation::default(),
+        };
+        assert!(playfield.lock_piece(&fp).is_err());
+        assert!(matches!(playfield.grid.cell(1, 0), Cell::Empty));
+    }
+
+    #[test]
+    fn event_log() {
+        let grid = PieceGrid::new(10, 40, vec![]);
+        let mut game = new_game(grid, None, vec![TetroPiece::T, TetroPiece::O].into());
+        game.step_n(Input::default(), 3);
+        assert!(game.drain_events().is_empty());
+
+        game.enable_event_log(100);
+        let mut expected = Vec::new();
+        for _ in 0..5 {
+            game.update(Input::default());
+            expected.extend(game.data().events.iter().map(|e| format!("{:?}", e)));
+        }
+        let events = game.drain_events();
+        assert_eq!(
+            expected,
+            events
+                .iter()
+                .map(|e| format!("{:?}", e))
+                .collect::<Vec<_>>()
+        );
+        assert!(game.drain_events().is_empty());
+
+        game.enable_event_log(3);
+        game.step_n(Input::default(), 5);
+        let events = game.drain_events();
+        assert_eq!(3, events.len());
+        assert!(events.iter().all(|e| matches!(e, GameEvent::Update(_))));
+    }
+
+    #[test]
+    fn prepend_next_pieces() {
+        let grid = PieceGrid::new(10, 40, vec![]);
+        let mut game = new_game(grid, None, vec![TetroPiece::T, TetroPiece::O].into());
+        game.prepend_next_pieces(vec![TetroPiece::I, TetroPiece::S].into());
+        assert_eq!(
+            &VecDeque::from(vec![
+                TetroPiece::I,
+                TetroPiece::S,
+                TetroPiece::T,
+                TetroPiece::O
+            ]),
+            game.next_pieces()
+        );
+        assert!(game.update_until(GameStateId::Play, 100));
+        assert_eq!(TetroPiece::I, game.data().falling_piece.unwrap().piece);
+
+        game.clear_next_pieces();
+        assert!(game.next_pieces().is_empty());
+    }
+
+    #[test]
+    fn lock_delay_move_reset_cap() {
+        fn frames_to_lock(max_lock_delay_resets: usize) -> Option<usize> {
+            let params = GameParams {
+                gravity: 0.0,
+                lock_delay: 10,
+                lock_delay_reset: LockDelayReset::MoveReset,
+                max_lock_delay_resets,
+                ..GameParams::default()
+            };
+            let fp = FallingPiece {
+                piece: TetroPiece::O,
+                x: 3,
+                y: -1,
+                rotation: Rotation::default(),
+            };
+            let grid = PieceGrid::new(10, 40, vec![]);
+            let mut game = new_game_with_params(params, grid, Some(fp), VecDeque::new());
+            game.update(Input::default());
+            assert_eq!(
+                0,
+                game.data()
+                    .falling_piece
+                    .unwrap()
+                    .droppable_rows(&game.data().playfield)
+            );
+            for i in 0..200 {
+                let input = if i % 2 == 0 {
+                    Input::MOVE_LEFT
+                } else {
+                    Input::MOVE_RIGHT
+                };
+                game.update(input);
+                if game.state_id() == GameStateId::Lock {
+                    return Some(i);
+                }
+            }
+            None
+        }
+        assert_eq!(None, frames_to_lock(1000));
+        let n = frames_to_lock(3).unwrap();
+        assert!(n > 10 && n < 50, "{}", n);
+        assert!(frames_to_lock(0).unwrap() <= 10);
+    }
+
+    #[test]
+    fn hold_once_per_piece() {
+        let grid = PieceGrid::new(10, 40, vec![]);
+        let mut game = new_game(
+            grid,
+            None,
+            vec![TetroPiece::T, TetroPiece::O, TetroPiece::I].into(),
+        );
+        assert!(game.update_until(GameStateId::Play, 100));
+        game.update(Input::HOLD);
+        assert!(game.data().is_hold_used());
+        assert_eq!(Some(TetroPiece::T), game.data().hold_piece);
+        assert_eq!(TetroPiece::O, game.data().falling_piece.unwrap().piece);
+        game.update(Input::default());
+        game.update(Input::HOLD);
+        assert!(game
+            .data()
+            .events
+            .iter()
+            .any(|e| matches!(e, GameEvent::HoldRejected)));
+        assert_eq!(Some(TetroPiece::T), game.data().hold_piece);
+        assert_eq!(TetroPiece::O, game.data().falling_piece.unwrap().piece);
+
+        // next piece can hold again
+        game.update(Input::HARD_DROP);
+        assert!(game.update_until(GameStateId::Play, 100));
+        assert!(!game.data().is_hold_used());
+        game.update(Input::HOLD);
+        assert_eq!(Some(TetroPiece::I), game.data().hold_piece);
+    }
+
+    #[test]
+    fn rejected_hold_does_not_freeze() {
+        let params = GameParams {
+            gravity: 1.0,
+            ..GameParams::default()
+        };
+        let grid = PieceGrid::new(10, 40, vec![]);
+        let next = vec![TetroPiece::T, TetroPiece::O, TetroPiece::I].into();
+        let mut game = new_game_with_params(params, grid, None, next);
+        assert!(game.update_until(GameStateId::Play, 100));
+        game.update(Input::HOLD);
+        let fp = game.data().falling_piece.unwrap();
+        game.update(Input::default());
+        game.update(Input::HOLD | Input::MOVE_LEFT);
+        assert!(game
+            .data()
+            .events
+            .iter()
+            .any(|e| matches!(e, GameEvent::HoldRejected)));
+        let moved = game.data().falling_piece.unwrap();
+        assert_eq!(fp.x - 1, moved.x);
+        assert_eq!(fp.y - 2, moved.y);
+    }
+
+    #[test]
+    fn hold_enabled_and_limit() {
+        let pieces = || vec![TetroPiece::T, TetroPiece::O, TetroPiece::I];
+        let params = GameParams {
+            hold_enabled: false,
+            ..GameParams::default()
+        };
+        let grid = PieceGrid::new(10, 40, vec![]);
+        let mut game = new_game_with_params(params, grid, None, pieces().into());
+        assert!(game.update_until(GameStateId::Play, 100));
+        game.update(Input::HOLD);
+        assert!(!game.data().is_hold_used());
+        assert_eq!(None, game.data().hold_piece);
+        assert_eq!(TetroPiece::T, game.data().falling_piece.unwrap().piece);
+
+        let params = GameParams {
+            hold_limit: Some(0),
+            ..GameParams::default()
+        };
+        let grid = PieceGrid::new(10, 40, vec![]);
+        let mut game = new_game_with_params(params, grid, None, pieces().into());
+        assert!(game.update_until(GameStateId::Play, 100));
+        for &(hold, falling) in &[
+            (TetroPiece::T, TetroPiece::O),
+            (TetroPiece::O, TetroPiece::T),
+            (TetroPiece::T, TetroPiece::O),
+        ] {
+            game.update(Input::HOLD);
+            game.update(Input::default());
+            assert_eq!(Some(hold), game.data().hold_piece);
+            assert_eq!(falling, game.data().falling_piece.unwrap().piece);
+        }
+        assert_eq!(3, game.data().num_holds_this_piece);
+    }
+
+    #[test]
+    fn fast_forward() {
+        let params = GameParams {
+            lock_flash_delay: 3,
+            line_clear_delay: 10.into(),
+            line_clear_are: Some(10),
+            ..GameParams::default()
+        };
+        let mut game = new_line_clear_game(params);
+        let events = game.fast_forward();
+        assert_eq!(GameStateId::Play, game.state_id());
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, GameEvent::EnterState(GameStateId::Play))));
+        // No effect in the play state.
+        assert!(game.fast_forward().is_empty());
+
+        game.update(Input::HARD_DROP);
+        let frame_num = game.frame_num();
+        let events = game.fast_forward();
+        assert_eq!(GameStateId::Play, game.state_id());
+        assert_eq!(TetroPiece::T, game.data().falling_piece.unwrap().piece);
+        assert!(game.frame_num() - frame_num > 20);
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, GameEvent::LineCleared(1, TSpin::None))));
+    }
+
+    #[test]
+    fn is_movable_to() {
+        /// Forbid the falling piece to enter column 0.
+        #[derive(Debug, Default)]
+        struct WalledLogic(WorldRuleLogic);
+        impl GameLogic<TetroPiece> for WalledLogic {
+            fn spawn_piece(
+                &self,
+                piece: TetroPiece,
+                playfield: &Playfield<TetroPiece>,
+            ) -> SpawnResult<TetroPiece> {
+                self.0.spawn_piece(piece, playfield)
+            }
+            fn rotate(
+                &self,
+                cw: bool,
+                fp: &FallingPiece<TetroPiece>,
+                playfield: &Playfield<TetroPiece>,
+            ) -> Option<RotationResult<TetroPiece>> {
+                self.0
+                    .rotate_if(cw, fp, playfield, |fp| self.is_movable_to(fp, playfield))
+            }
+            fn is_movable_to(
+                &self,
+                fp: &FallingPiece<TetroPiece>,
+                playfield: &Playfield<TetroPiece>,
+            ) -> bool {
+                fp.can_put_onto(playfield) && fp.bounding_rect().0.start > 0
+            }
+        }
+
+        let config = GameConfig {
+            params: GameParams::default(),
+            logic: WalledLogic::default(),
+        };
+        let playfield = Playfield {
+            visible_rows: 20,
+            grid: PieceGrid::new(10, 40, vec![]),
+        };
+        let fp = FallingPiece {
+            piece: TetroPiece::O,
+            x: 0,
+            y: 10,
+            rotation: Rotation::default(),
+        };
+        let mut game = GameBuilder::new(config, playfield)
+            .falling_piece(fp)
+            .next_pieces(vec![TetroPiece::T].into())
+            .build()
+            .unwrap();
+        // The O piece occupies columns 1-2.
+        assert_eq!(1..3, game.data().falling_piece.unwrap().bounding_rect().0);
+        game.update(Input::MOVE_LEFT);
+        assert_eq!(0, game.data().falling_piece.unwrap().x);
+        game.update(Input::default());
+        game.update(Input::MOVE_RIGHT);
+        assert_eq!(1, game.data().falling_piece.unwrap().x);
+
+        // Kick 0 of R -> 2 enters column 0, so kick 1 (+1, 0) is used.
+        let config = GameConfig {
+            params: GameParams::default(),
+            logic: WalledLogic::default(),
+        };
+        let playfield = Playfield {
+            visible_rows: 20,
+            grid: PieceGrid::new(10, 40, vec![]),
+        };
+        let fp = FallingPiece {
+            piece: TetroPiece::T,
+            x: 0,
+            y: 10,
+            rotation: Rotation::Cw90,
+        };
+        let mut game = GameBuilder::new(config, playfield)
+            .falling_piece(fp)
+            .next_pieces(vec![TetroPiece::T].into())
+            .build()
+            .unwrap();
+        assert_eq!(1..3, game.data().falling_piece.unwrap().bounding_rect().0);
+        game.update(Input::ROTATE_CW);
+        let rotated = game.data().falling_piece.unwrap();
+        assert_eq!(Rotation::Cw180 as usize, rotated.rotation as usize);
+        assert_eq!((1, 10), (rotated.x, rotated.y));
+    }
+
+    #[test]
+    fn hold_preserves_state() {
+        fn rotation_after_swap(hold_preserves_state: bool) -> Rotation {
+            let params = GameParams {
+                hold_preserves_state,
+                ..GameParams::default()
+            };
+            let grid = PieceGrid::new(10, 40, vec![]);
+            let mut game = new_game_with_params(
+                params,
+                grid,
+                None,
+                vec![TetroPiece::T, TetroPiece::O, TetroPiece::I].into(),
+            );
+            assert!(game.update_until(GameStateId::Play, 100));
+            let spawned = game.data().falling_piece.unwrap();
+            game.update(Input::ROTATE_CW);
+            game.update(Input::FIRM_DROP);
+            assert!(game.data().falling_piece.unwrap().y < spawned.y);
+            game.update(Input::HOLD);
+            assert_eq!(Some(TetroPiece::T), game.data().hold_piece);
+            game.update(Input::HARD_DROP);
+            assert!(game.update_until(GameStateId::Play, 100));
+            game.update(Input::HOLD);
+            let fp = game.data().falling_piece.unwrap();
+            assert_eq!(TetroPiece::T, fp.piece);
+            // Not at the position when held.
+            assert_eq!((spawned.x, spawned.y), (fp.x, fp.y));
+            fp.rotation
+        }
+        assert_eq!(Rotation::Cw90 as usize, rotation_after_swap(true) as usize);
+        assert_eq!(Rotation::Cw0 as usize, rotation_after_swap(false) as usize);
+    }
+
+    #[test]
+    fn game_over_reason() {
+        let mut grid = PieceGrid::new(10, 40, vec![]);
+        for y in 0..22 {
+            grid.set_cell(0, y, Cell::Garbage);
+            grid.set_cell(4, y, Cell::Garbage);
+        }
+        let mut game = new_game(grid, None, vec![TetroPiece::T].into());
+        assert!(!game.is_game_over());
+        assert_eq!(None, game.game_over_reason());
+        assert!(game.update_until(GameStateId::GameOver, 10));
+        assert!(game.is_game_over());
+        assert_eq!(Some(GameOverReason::BlockOut), game.game_over_reason());
+    }
+
+    #[test]
+    fn transition_validator() {
+        let mut grid = PieceGrid::new(10, 40, vec![]);
+        for y in 0..22 {
+            grid.set_cell(0, y, Cell::Garbage);
+            grid.set_cell(4, y, Cell::Garbage);
+        }
+        let mut game = new_game(grid.clone(), None, vec![TetroPiece::T].into());
+        game.set_transition_validator(Some(TransitionValidator::standard()));
+        assert!(game.update_until(GameStateId::GameOver, 10));
+
+        let mut validator = TransitionValidator::default();
+        validator.allow(GameStateId::Init, GameStateId::SpawnPiece);
+        let mut game = new_game(grid, None, vec![TetroPiece::T].into());
+        game.set_transition_validator(Some(validator));
+        assert!(game.update_until(GameStateId::Error, 10));
+        assert!(!game
+            .data()
+            .events
+            .iter()
+            .any(|e| matches!(e, GameEvent::EnterState(GameStateId::GameOver))));
+    }
+
+    #[test]
+    fn transition_validator_place() {
+        let params = GameParams {
+            line_goal: Some(1),
+            ..GameParams::default()
+        };
+        let mut grid = PieceGrid::new(10, 40, vec![]);
+        for x in 0..8 {
+            grid.set_cell(x, 0, Cell::Garbage);
+        }
+        let mut game = new_game_with_params(params, grid, None, vec![TetroPiece::T].into());
+        game.set_transition_validator(Some(TransitionValidator::standard()));
+        assert_eq!(Ok(1), game.place(TetroPiece::O, 7, Rotation::Cw0));
+        assert_eq!(GameStateId::Finished, game.state_id());
+
+        // Lock out on the column 4.
+        let mut grid = PieceGrid::new(10, 40, vec![]);
+        for y in 0..20 {
+            grid.set_cell(4, y, Cell::Garbage);
+        }
+        let mut game = new_game(grid.clone(), None, vec![TetroPiece::T].into());
+        game.set_transition_validator(Some(TransitionValidator::standard()));
+        assert!(game.place(TetroPiece::O, 3, Rotation::Cw0).is_err());
+        assert_eq!(GameStateId::GameOver, game.state_id());
+
+        // During the line clear delay.
+        let params = GameParams {
+            line_clear_delay: 10.into(),
+            ..GameParams::default()
+        };
+        for x in 0..9 {
+            grid.set_cell(x, 0, Cell::Garbage);
+        }
+        let fp = FallingPiece {
+            piece: TetroPiece::I,
+            x: 7,
+            y: 10,
+            rotation: Rotation::Cw90,
+        };
+        let mut game = new_game_with_params(params, grid, Some(fp), vec![TetroPiece::T].into());
+        game.set_transition_validator(Some(TransitionValidator::standard()));
+        game.update(Input::default());
+        game.update(Input::HARD_DROP);
+        assert!(game.update_until(GameStateId::LineClear, 10));
+        assert!(game.place(TetroPiece::O, 3, Rotation::Cw0).is_err());
+        assert_eq!(GameStateId::GameOver, game.state_id());
+    }
+
+    #[test]
+    fn game_over_event_on_block_out() {
+        // hold
+        let fp = FallingPiece {
+            piece: TetroPiece::T,
+            x: 3,
+            y: 0,
+            rotation: Rotation::default(),
+        };
+        let mut game = new_game(blocked_spawn_grid(), Some(fp), vec![TetroPiece::T].into());
+        game.update(Input::default());
+        assert_eq!(GameStateId::Play, game.state_id());
+        game.update(Input::HOLD);
+        assert_eq!(GameStateId::GameOver, game.state_id());
+        assert!(game
+            .data()
+            .events
+            .iter()
+            .any(|e| matches!(e, GameEvent::GameOver(GameOverReason::BlockOut))));
+
+        // spawn
+        let mut game = new_game(blocked_spawn_grid(), None, vec![TetroPiece::T].into());
+        game.update(Input::default());
+        assert_eq!(GameStateId::SpawnPiece, game.state_id());
+        game.update(Input::default());
+        assert_eq!(GameStateId::GameOver, game.state_id());
+        assert!(game.data().falling_piece.is_none());
+        assert!(game
+            .data()
+            .events
+            .iter()
+            .any(|e| matches!(e, GameEvent::GameOver(GameOverReason::BlockOut))));
+    }
+
+    #[test]
+    fn game_over_reason_on_lock() {
+        fn reason_after_drop(stack_height: usize) -> Option<GameOverReason> {
+            let params = GameParams {
+                top_out_condition: TopOutCondition::LOCK_OUT | TopOutCondition::PARTIAL_LOCK_OUT,
+                ..GameParams::default()
+            };
+            let mut grid = PieceGrid::new(10, 40, vec![]);
+            for y in 0..stack_height {
+                for x in 3..7 {
+                    grid.set_cell(x, y, Cell::Garbage);
+                }
+            }
+            let fp = FallingPiece {
+                piece: TetroPiece::O,
+                x: 3,
+                y: 30,
+                rotation: Rotation::default(),
+            };
+            let mut game = new_game_with_params(params, grid, Some(fp), vec![TetroPiece::T].into());
+            game.update(Input::default());
+            game.update(Input::HARD_DROP);
+            game.update_until(GameStateId::GameOver, 10);
+            game.game_over_reason()
+        }
+        // The O piece occupies rows 19-20.
+        assert_eq!(Some(GameOverReason::PartialLockOut), reason_after_drop(19));
+        // The O piece occupies rows 22-23, entirely in the vanish zone.
+        assert_eq!(Some(GameOverReason::LockOut), reason_after_drop(22));
+        assert_eq!(None, reason_after_drop(10));
+    }
+
+    #[test]
+    fn basic_input_manager_das_arr() {
+        let params = GameParams {
+            gravity: 0.0,
+            das: 6,
+            arr: 3,
+            ..GameParams::default()
+        };
+        let fp = FallingPiece {
+            piece: TetroPiece::T,
+            x: 7,
+            y: 10,
+            rotation: Rotation::default(),
+        };
+        let grid = PieceGrid::new(10, 40, vec![]);
+        let mut game = new_game_with_params(params, grid, Some(fp), VecDeque::new());
+        game.update(Input::default());
+        let mut moved_frames = Vec::new();
+        for frame in 0..16 {
+            let x = game.data().falling_piece.unwrap().x;
+            game.update(Input::MOVE_LEFT);
+            if game.data().falling_piece.unwrap().x < x {
+                moved_frames.push(frame);
+            }
+        }
+        // At the press, after DAS, then every ARR.
+        assert_eq!(vec![0, 6, 9, 12, 15], moved_frames);
+    }
+
+    #[test]
+    fn basic_input_manager_soft_drop() {
+        let params = GameParams {
+            gravity: 0.0,
+            soft_drop_gravity: 1.0,
+            ..GameParams::default()
+        };
+        let fp = FallingPiece {
+            piece: TetroPiece::T,
+            x: 3,
+            y: 10,
+            rotation: Rotation::default(),
+        };
+        let grid = PieceGrid::new(10, 40, vec![]);
+        let mut game = new_game_with_params(params, grid, Some(fp), VecDeque::new());
+        game.update(Input::default());
+        // Dropped every frame while held, not only at the press.
+        game.step_n(Input::SOFT_DROP, 5);
+        assert_eq!(fp.y - 5, game.data().falling_piece.unwrap().y);
+        game.step_n(Input::default(), 5);
+        assert_eq!(fp.y - 5, game.data().falling_piece.unwrap().y);
+    }
+
+    #[test]
+    fn das_during_are() {
+        fn moved_after_spawn(das_during_are: bool) -> i32 {
+            let params = GameParams {
+                das: 5,
+                arr: 2,
+                are: 10,
+                das_during_are,
+                ..GameParams::default()
+            };
+            let grid = PieceGrid::new(10, 40, vec![]);
+            let mut game = new_game_with_params(params, grid, None, vec![TetroPiece::T].into());
+            for _ in 0..100 {
+                if game.state_id() == GameStateId::Play {
+                    break;
+                }
+                game.update(Input::MOVE_LEFT);
+            }
+            let x = game.data().falling_piece.unwrap().x;
+            for _ in 0..3 {
+                game.update(Input::MOVE_LEFT);
+            }
+            x - game.data().falling_piece.unwrap().x
+        }
+        // Shift on the first frame, then by ARR.
+        assert_eq!(2, moved_after_spawn(true));
+        // Shift on the first frame, then wait for DAS.
+        assert_eq!(1, moved_after_spawn(false));
+    }
+
+    #[test]
+    fn resimulate() {
+        let new = || {
+            let grid = PieceGrid::new(10, 40, vec![]);
+            let pieces = vec![TetroPiece::T, TetroPiece::O, TetroPiece::I, TetroPiece::S];
+            let mut game = new_game(grid, None, pieces.into());
+            assert!(game.update_until(GameStateId::Play, 100));
+            game
+        };
+        let inputs = [
+            Input::MOVE_LEFT,
+            Input::default(),
+            Input::ROTATE_CW,
+            Input::HARD_DROP,
+            Input::default(),
+            Input::MOVE_RIGHT,
+        ];
+        // The input manager is compared by input since the order of `HashMap` differs.
+        fn assert_same_data(expected: &GameData<TetroPiece>, actual: &GameData<TetroPiece>) {
+            let summary = |d: &GameData<TetroPiece>| {
+                format!(
+                    "{:?} {:?} {:?} {:?}",
+                    d.playfield, d.falling_piece, d.hold_piece, d.next_pieces
+                )
+            };
+            assert_eq!(summary(expected), summary(actual));
+            let inputs = expected.input_manager.inputs();
+            assert_eq!(inputs.len(), actual.input_manager.inputs().len());
+            for (input, counter) in inputs {
+                assert_eq!(Some(counter), actual.input_manager.inputs().get(input));
+            }
+        }
+        let mut expected = new();
+        for input in &inputs {
+            expected.update(*input);
+        }
+
+        let mut game = new();
+        game.update(inputs[0]);
+        let snapshot = game.save_state();
+        assert_eq!(game.frame_num(), snapshot.frame_num());
+        for _ in 1..inputs.len() {
+            game.update(Input::MOVE_RIGHT);
+        }
+        assert_ne!(
+            format!("{:?}", expected.data().falling_piece),
+            format!("{:?}", game.data().falling_piece)
+        );
+
+        game.resimulate(snapshot, &inputs[1..]);
+        assert_eq!(expected.frame_num(), game.frame_num());
+        assert_eq!(expected.state_id(), game.state_id());
+        assert_same_data(expected.data(), game.data());
+    }
+
+    #[test]
+    fn update_until_play() {
+        let grid = PieceGrid::new(10, 40, vec![]);
+        let mut game = new_game(grid, None, TetroPiece::slice().to_vec().into());
+        assert_eq!(GameStateId::Init, game.state_id());
+        let max_frames = 100;
+        assert!(game.update_until(GameStateId::Play, max_frames));
+        assert_eq!(GameStateId::Play, game.state_id());
+        let n = game.frame_num();
+        assert!(0 < n && n <= max_frames as Frames);
+        assert!(n > game.config().params.are);
+
+        game.step_n(Input::default(), 3);
+        assert_eq!(n + 3, game.frame_num());
+        assert!(!game.update_until(GameStateId::LineClear, 10));
+    }
+
+    #[test]
+    fn line_clear_delay_by_lines() {
+        let delay = LineClearDelay {
+            normal: [10, 20, 30, 40],
+            tspin: [15, 25, 35, 45],
+        };
+        assert_eq!(0, delay.get(0, TSpin::None));
+        assert_eq!(10, delay.get(1, TSpin::None));
+        assert_eq!(40, delay.get(4, TSpin::None));
+        assert_eq!(25, delay.get(2, TSpin::Mini));
+        assert_eq!(35, delay.get(3, TSpin::Normal));
+        assert_eq!(LineClearDelay::uniform(5), 5.into());
+
+        // Return the number of frames spent in LineClear after a vertical I is hard dropped at
+        // the left wall.
+        let frames_to_spawn = |num_rows: usize| {
+            let mut grid = PieceGrid::new(10, 40, vec![]);
+            for y in 0..num_rows {
+                for x in 1..10 {
+                    grid.set_cell(x, y, Cell::Garbage);
+                }
+            }
+            let fp = FallingPiece {
+                piece: TetroPiece::I,
+                x: -2,
+                y: 10,
+                rotation: Rotation::Cw90,
+            };
+            let params = GameParams {
+                line_clear_delay: delay,
+                ..GameParams::default()
+            };
+            let mut game = new_game_with_params(params, grid, Some(fp), vec![TetroPiece::T].into());
+            game.update(Input::default());
+            game.update(Input::HARD_DROP);
+            assert!(game.update_until(GameStateId::LineClear, 10));
+            let started_at = game.frame_num();
+            assert!(game.update_until(GameStateId::SpawnPiece, 100));
+            game.frame_num() - started_at
+        };
+        let single = frames_to_spawn(1);
+        let tetris = frames_to_spawn(4);
+        assert_eq!(delay.normal[0] + 1, single);
+        assert_eq!(delay.normal[3] + 1, tetris);
+        assert!(tetris > single);
+    }
+
+    #[test]
+    fn falling_piece_try_move_and_rotate() {
+        let mut grid = PieceGrid::new(10, 40, vec![]);
+        grid.set_cell(6, 0, Cell::Garbage);
+        let playfield = Playfield {
+            visible_rows: 20,
+            grid,
+        };
+        // T at x=3..=5, y=0..=1
+        let fp = FallingPiece {
+            piece: TetroPiece::T,
+            x: 3,
+            y: -1,
+            rotation: Rotation::default(),
+        };
+        assert!(fp.can_put_onto(&playfield));
+        assert!(fp.try_move(1, 0, &playfield).is_none());
+        assert!(fp.try_move(0, -1, &playfield).is_none());
+        let moved = fp.try_move(-1, 0, &playfield).unwrap();
+        assert_eq!((2, -1), (moved.x, moved.y));
+
+        let logic = WorldRuleLogic::default();
+        let r = fp.try_rotate(true, &logic, &playfield).unwrap();
+        assert!(r.falling_piece.can_put_onto(&playfield));
+        assert_eq!(TSpin::None, r.tspin);
+    }
+
+    #[test]
+    fn game_builder_seed() {
+        let config = GameConfig {
+            params: GameParams::default(),
+            logic: WorldRuleLogic::default(),
+        };
+        let playfield = Playfield {
+            visible_rows: 20,
+            grid: PieceGrid::new(10, 40, vec![]),
+        };
+        let game = GameBuilder::new(config, playfield)
+            .next_pieces(vec![TetroPiece::T, TetroPiece::O].into())
+            .seed(42)
+            .build()
+            .unwrap();
+        assert_eq!(Some(42), game.seed());
+    }
+
+    #[test]
+    fn after_lock_rng() {
+        /// Put garbage at a random column of row 30 on each lock.
+        #[derive(Debug, Default)]
+        struct RandomGarbageLogic(WorldRuleLogic);
+
+        impl GameLogic<TetroPiece> for RandomGarbageLogic {
+            fn spawn_piece(
+                &self,
+                piece: TetroPiece,
+                playfield: &Playfield<TetroPiece>,
+            ) -> SpawnResult<TetroPiece> {
+                self.0.spawn_piece(piece, playfield)
+            }
+            fn rotate(
+                &self,
+                cw: bool,
+                falling_piece: &FallingPiece<TetroPiece>,
+                playfield: &Playfield<TetroPiece>,
+            ) -> Option<RotationResult<TetroPiece>> {
+                self.0.rotate(cw, falling_piece, playfield)
+            }
+            fn after_lock(
+                &self,
+                _falling_piece: &FallingPiece<TetroPiece>,
+                playfield: &mut Playfield<TetroPiece>,
+                rng: &mut GameRng,
+            ) {
+                let x = rng.gen_below(playfield.grid.num_cols());
+                playfield.grid.set_cell(x, 30, Cell::Garbage);
+            }
+        }
+
+        fn play(seed: u64) -> PieceGrid {
+            let config = GameConfig {
+                params: GameParams::default(),
+                logic: RandomGarbageLogic::default(),
+            };
+            let playfield = Playfield {
+                visible_rows: 20,
+                grid: PieceGrid::new(10, 40, vec![]),
+            };
+            let mut game = GameBuilder::new(config, playfield)
+                .next_pieces(TetroPiece::slice().iter().copied().collect())
+                .seed(seed)
+                .build()
+                .unwrap();
+            for _ in 0..5 {
+                game.update(Input::HARD_DROP);
+                game.fast_forward();
+            }
+            assert_eq!(GameStateId::Play, game.state_id());
+            game.data().playfield.grid.clone()
+        }
+
+        let grid = play(1);
+        assert!(grid.count_filled_in(0..10, 30..31) > 0);
+        assert!(grid.equals_by(&play(1), Cell::eq_ignoring_ghost));
+        assert!(!grid.equals_by(&play(2), Cell::eq_ignoring_ghost));
+    }
+
+    #[test]
+    fn game_builder_tspin_triple() {
+        let playfield = playfield_from_rows(&[
+            ".X........",
+            "..........",
+            "X.XXXXXXXX",
+            "..XXXXXXXX",
+            "X.XXXXXXXX",
+        ]);
+        let config = GameConfig {
+            params: GameParams::default(),
+            logic: WorldRuleLogic::default(),
+        };
+        let mut game = GameBuilder::new(config, playfield)
+            .falling_piece(FallingPiece {
+                piece: TetroPiece::T,
+                x: 0,
+                y: 2,
+                rotation: Rotation::Cw180,
+            })
+            .hold_piece(TetroPiece::I)
+            .next_pieces(vec![TetroPiece::O].into())
+            .build()
+            .unwrap();
+        assert_eq!(GameStateId::Play, game.state_id());
+        assert_eq!(Some(TetroPiece::I), game.data().hold_piece);
+        assert_eq!(None, game.seed());
+
+        game.update(Input::ROTATE_CW);
+        let fp = game.data().falling_piece.unwrap();
+        assert_eq!((0, 0), (fp.x, fp.y));
+        assert_eq!(TSpin::Normal, game.data().tspin);
+
+        game.update(Input::HARD_DROP);
+        assert!(game.update_until(GameStateId::LineClear, 10));
+        game.update(Input::default());
+        assert!(game
+            .data()
+            .events
+            .iter()
+            .any(|e| matches!(e, GameEvent::LineCleared(3, TSpin::Normal))));
+        assert!(game
+            .data()
+            .events
+            .iter()
+            .any(|e| matches!(e, GameEvent::AttackSent(6))));
+        assert!(game.data().back_to_back);
+        assert_eq!(0, game.data().combo);
+    }
+
+    #[test]
+    fn playfield_height_profile_and_clear() {
+        let mut playfield = playfield_from_rows(&["X.........", "X.......X.", "XX......X."]);
+        assert!(!playfield.is_empty());
+        assert_eq!(
+            vec![3, 1, 0, 0, 0, 0, 0, 0, 2, 0],
+            playfield.height_profile()
+        );
+        playfield.clear();
+        assert!(playfield.is_empty());
+        assert_eq!(vec![0; 10], playfield.height_profile());
+    }
+
+    #[test]
+    fn set_gravity_at_runtime() {
+        let config = GameConfig {
+            params: GameParams {
+                gravity: 0.0,
+                ..GameParams::default()
+            },
+            logic: WorldRuleLogic::default(),
+        };
+        let mut game = GameBuilder::new(config, playfield_from_rows(&[]))
+            .falling_piece(FallingPiece {
+                piece: TetroPiece::T,
+                x: 3,
+                y: 15,
+                rotation: Rotation::default(),
+            })
+            .build()
+            .unwrap();
+        game.step_n(Input::default(), 3);
+        assert_eq!(15, game.data().falling_piece.unwrap().y);
+
+        game.set_gravity(1.0);
+        game.update(Input::default());
+        assert_eq!(14, game.data().falling_piece.unwrap().y);
+        game.update(Input::default());
+        assert_eq!(13, game.data().falling_piece.unwrap().y);
+
+        game.set_params(GameParams {
+            gravity: 0.0,
+            ..game.config().params
+        });
+        game.set_lock_delay(1);
+        assert_eq!(1, game.config().params.lock_delay);
+        game.update(Input::default());
+        assert_eq!(13, game.data().falling_piece.unwrap().y);
+    }
 }
@@ -0,0 +1,101 @@
+//! Attack (garbage lines sent) calculation for versus modes.
+//!
+//! References:
+//! * https://harddrop.com/wiki/Garbage
+//! * https://tetris.fandom.com/wiki/Tetris_Guideline
+
+use super::common::TSpin;
+
+/// Indexed by combo count, and the last value is used for longer combos.
+const COMBO_TABLE: [usize; 11] = [0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+
+/// Lines sent by a perfect clear, which replaces the base attack and back-to-back bonus.
+const PERFECT_CLEAR_ATTACK: usize = 10;
+
+/// Return the number of garbage lines sent by a line clear.
+///
+/// * `combo`: the number of consecutive line clears before this one, or negative if none.
+/// * `b2b`: whether this clear continues a back-to-back chain of Tetrises and T-Spins.
+///
+/// | Clear          | Lines |
+/// |----------------|-------|
+/// | Single         | 0     |
+/// | Double         | 1     |
+/// | Triple         | 2     |
+/// | Tetris         | 4     |
+/// | T-Spin Mini    | lines - 1 |
+/// | T-Spin Single  | 2     |
+/// | T-Spin Double  | 4     |
+/// | T-Spin Triple  | 6     |
+/// | Back-to-back   | +1    |
+/// | Perfect clear  | 10    |
+pub fn attack_lines(
+    lines: usize,
+    tspin: TSpin,
+    combo: i32,
+    b2b: bool,
+    perfect_clear: bool,
+) -> usize {
+    if lines == 0 {
+        return 0;
+    }
+    let base = if perfect_clear {
+        PERFECT_CLEAR_ATTACK
+    } else {
+        let base = match tspin {
+            TSpin::None => match lines {
+                1 => 0,
+                2 => 1,
+                3 => 2,
+                _ => 4,
+            },
+            TSpin::Mini => lines - 1,
+            TSpin::Normal => lines * 2,
+        };
+        if b2b {
+            base + 1
+        } else {
+            base
+        }
+    };
+    let combo_bonus = if combo < 0 {
+        0
+    } else {
+        COMBO_TABLE[core::cmp::min(combo as usize, COMBO_TABLE.len() - 1)]
+    };
+    base + combo_bonus
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attack_table() {
+        assert_eq!(0, attack_lines(0, TSpin::Mini, -1, false, false));
+        assert_eq!(0, attack_lines(1, TSpin::None, -1, false, false));
+        assert_eq!(1, attack_lines(2, TSpin::None, -1, false, false));
+        assert_eq!(2, attack_lines(3, TSpin::None, -1, false, false));
+        assert_eq!(4, attack_lines(4, TSpin::None, -1, false, false));
+        assert_eq!(5, attack_lines(4, TSpin::None, -1, true, false));
+        assert_eq!(0, attack_lines(1, TSpin::Mini, -1, false, false));
+        assert_eq!(1, attack_lines(2, TSpin::Mini, -1, false, false));
+        assert_eq!(2, attack_lines(1, TSpin::Normal, -1, false, false));
+        assert_eq!(4, attack_lines(2, TSpin::Normal, -1, false, false));
+        assert_eq!(5, attack_lines(2, TSpin::Normal, -1, true, false));
+        assert_eq!(6, attack_lines(3, TSpin::Normal, -1, false, false));
+        assert_eq!(10, attack_lines(4, TSpin::None, -1, true, true));
+    }
+
+    #[test]
+    fn combo_bonus() {
+        assert_eq!(0, attack_lines(1, TSpin::None, 0, false, false));
+        assert_eq!(1, attack_lines(1, TSpin::None, 1, false, false));
+        assert_eq!(2, attack_lines(2, TSpin::None, 2, false, false));
+        assert_eq!(6, attack_lines(2, TSpin::Normal, 3, false, false));
+        assert_eq!(3, attack_lines(1, TSpin::None, 6, false, false));
+        assert_eq!(5, attack_lines(1, TSpin::None, 10, false, false));
+        assert_eq!(5, attack_lines(1, TSpin::None, 20, false, false));
+        assert_eq!(11, attack_lines(1, TSpin::None, 1, false, true));
+    }
+}
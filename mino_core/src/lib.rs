@@ -1,7 +1,14 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[macro_use]
+extern crate alloc;
 #[macro_use]
 extern crate bitflags;
 extern crate grid;
 extern crate input_counter;
 
 pub mod common;
+pub mod garbage;
+pub mod prelude;
 pub mod tetro;
+pub mod zobrist;
@@ -0,0 +1,15 @@
+//! Commonly used types.
+//!
+//! ```
+//! use mino_core::prelude::*;
+//! ```
+//!
+//! The `common::Piece` trait is exported as `PieceTrait` not to conflict with `tetro::Piece`.
+
+pub use super::common::{
+    Cell, ConflictPolicy, FallingPiece, Frames, Game, GameBuilder, GameConfig, GameData, GameEvent,
+    GameLogic, GameOverReason, GameParams, GameStateId, Gravity, Input, InputMapper,
+    LineClearDelay, LineClearGravity, LockDelayReset, Piece as PieceTrait, Playfield, Rotation,
+    SpawnResult, TSpin,
+};
+pub use super::tetro::{Piece, PieceGrid, WorldRuleLogic};
@@ -1,9 +1,17 @@
-use super::common::{FallingPiece, GameLogic, Piece as PieceTrait, Playfield, Rotation, TSpin};
+use super::common::{
+    FallingPiece, GameConfig, GameLogic, GameParams, IndexedPiece, Piece as PieceTrait, Playfield,
+    Rotation, RotationResult, SpawnResult, TSpin,
+};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use core::error::Error;
+use core::fmt;
+use core::str::FromStr;
 use grid::IsEmpty;
 use lazy_static::lazy_static;
+#[cfg(feature = "std")]
 use std::error::Error;
-use std::fmt;
-use std::str::FromStr;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Piece {
@@ -32,6 +40,10 @@ impl Piece {
         ];
         &PIECES
     }
+    /// Return the block positions in `self.grid(rotation)`, row by row from the bottom.
+    pub fn occupied_offsets(&self, rotation: Rotation) -> &'static [(usize, usize)] {
+        &PIECE_DEFINITIONS[*self as usize].offsets[rotation as usize]
+    }
 }
 
 impl fmt::Display for Piece {
@@ -64,6 +76,27 @@ pub type PieceGrid = super::common::PieceGrid<Piece>;
 
 pub struct PieceDefinition {
     grids: Vec<PieceGrid>,
+    /// The block positions in `grids` for each rotation.
+    offsets: Vec<Vec<(usize, usize)>>,
+}
+
+impl PieceDefinition {
+    fn new(grid: &PieceGrid) -> Self {
+        let grids: Vec<PieceGrid> = [
+            Rotation::Cw0,
+            Rotation::Cw90,
+            Rotation::Cw180,
+            Rotation::Cw270,
+        ]
+        .iter()
+        .map(|r| grid.rotate(*r))
+        .collect();
+        let offsets = grids
+            .iter()
+            .map(|g| g.iter_filled().map(|(x, y, _)| (x, y)).collect())
+            .collect();
+        Self { grids, offsets }
+    }
 }
 
 fn gen_piece_definitions() -> Vec<PieceDefinition> {
@@ -159,83 +192,31 @@ fn gen_piece_definitions() -> Vec<PieceDefinition> {
     grid_l.reverse_rows();
 
     vec![
-        // I
-        PieceDefinition {
-            grids: vec![
-                grid_i.clone(),
-                grid_i.rotate1(),
-                grid_i.rotate2(),
-                grid_i.rotate3(),
-            ],
-        },
-        // T
-        PieceDefinition {
-            grids: vec![
-                grid_t.clone(),
-                grid_t.rotate1(),
-                grid_t.rotate2(),
-                grid_t.rotate3(),
-            ],
-        },
-        // O
-        PieceDefinition {
-            grids: vec![
-                grid_o.clone(),
-                grid_o.rotate1(),
-                grid_o.rotate2(),
-                grid_o.rotate3(),
-            ],
-        },
-        // S
-        PieceDefinition {
-            grids: vec![
-                grid_s.clone(),
-                grid_s.rotate1(),
-                grid_s.rotate2(),
-                grid_s.rotate3(),
-            ],
-        },
-        // Z
-        PieceDefinition {
-            grids: vec![
-                grid_z.clone(),
-                grid_z.rotate1(),
-                grid_z.rotate2(),
-                grid_z.rotate3(),
-            ],
-        },
-        // J
-        PieceDefinition {
-            grids: vec![
-                grid_j.clone(),
-                grid_j.rotate1(),
-                grid_j.rotate2(),
-                grid_j.rotate3(),
-            ],
-        },
-        // L
-        PieceDefinition {
-            grids: vec![
-                grid_l.clone(),
-                grid_l.rotate1(),
-                grid_l.rotate2(),
-                grid_l.rotate3(),
-            ],
-        },
+        PieceDefinition::new(&grid_i),
+        PieceDefinition::new(&grid_t),
+        PieceDefinition::new(&grid_o),
+        PieceDefinition::new(&grid_s),
+        PieceDefinition::new(&grid_z),
+        PieceDefinition::new(&grid_j),
+        PieceDefinition::new(&grid_l),
     ]
 }
 
+/// SRS offsets of each rotation indexed by `Rotation as usize`, one per kick test.
+/// See `WorldRuleLogic::rotate` for references.
+pub type OffsetData = [Vec<(i32, i32)>; 4];
+
 lazy_static! {
     static ref PIECE_DEFINITIONS: Vec<PieceDefinition> = gen_piece_definitions();
-    static ref OFFSET_DATA_I: [Vec<(i32, i32)>; 4] = [
+    static ref OFFSET_DATA_I: OffsetData = [
         vec![(0, 0), (-1, 0), (2, 0), (-1, 0), (2, 0)],
         vec![(-1, 0), (0, 0), (0, 0), (0, 1), (0, -2)],
         vec![(-1, 1), (1, 1), (-2, 1), (1, 0), (-2, 0)],
         vec![(0, 1), (0, 1), (0, 1), (0, -1), (0, 2)],
     ];
-    static ref OFFSET_DATA_O: [Vec<(i32, i32)>; 4] =
+    static ref OFFSET_DATA_O: OffsetData =
         [vec![(0, 0)], vec![(0, -1)], vec![(-1, -1)], vec![(-1, 0)]];
-    static ref OFFSET_DATA_JLSTZ: [Vec<(i32, i32)>; 4] = [
+    static ref OFFSET_DATA_JLSTZ: OffsetData = [
         vec![(0, 0), (0, 0), (0, 0), (0, 0), (0, 0)],
         vec![(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
         vec![(0, 0), (0, 0), (0, 0), (0, 0), (0, 0)],
@@ -249,105 +230,709 @@ impl PieceTrait for Piece {
     }
 }
 
+impl IndexedPiece for Piece {
+    fn num_pieces() -> usize {
+        Self::num()
+    }
+    fn to_index(&self) -> usize {
+        *self as usize
+    }
+    fn from_index(index: usize) -> Option<Self> {
+        Self::slice().get(index).copied()
+    }
+}
+
 //---
 
-#[derive(Debug, Default)]
-pub struct WorldRuleLogic {}
+/// How spins are detected in `WorldRuleLogic::rotate`.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum SpinDetection {
+    /// Only T-Spins are detected by the 3-corner rule.
+    #[default]
+    TSpinOnly,
+    /// In addition to T-Spins, a rotation of another piece is credited as `TSpin::Mini` if the
+    /// piece can not move in any of four directions after the rotation.
+    AllSpinImmobile,
+}
 
-impl GameLogic<Piece> for WorldRuleLogic {
-    fn spawn_piece(&self, piece: Piece, playfield: &Playfield<Piece>) -> FallingPiece<Piece> {
-        let g = piece.grid(Rotation::default());
-        let top_pad = piece.grid_top_padding(Rotation::default());
-        let mut fp = FallingPiece {
-            piece: piece,
-            x: ((playfield.grid.num_cols() - g.num_cols()) as i32) / 2,
-            y: (playfield.visible_rows as i32) - (g.num_rows() - top_pad) as i32,
-            rotation: Rotation::default(),
-        };
-        if piece != Piece::I {
-            fp.y += 1;
+/// How T-Spin Mini is distinguished from proper T-Spin.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum TSpinMiniRule {
+    /// Mini if the cell behind the T is blocked and not all four corners are blocked.
+    #[default]
+    Classic,
+    /// Same as `Classic`, but a rotation with the last kick (index 4) is always proper.
+    Kick4IsFull,
+}
+
+fn is_immobile(fp: &FallingPiece<Piece>, playfield: &Playfield<Piece>) -> bool {
+    [(-1, 0), (1, 0), (0, -1), (0, 1)]
+        .iter()
+        .all(|(dx, dy)| fp.try_move(*dx, *dy, playfield).is_none())
+}
+
+/// Occupancy of the four corners around the center of a T piece.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TSpinCorners {
+    /// Bottom left, bottom right, top left and top right.
+    /// Cells out of the playfield are occupied.
+    pub occupied: [bool; 4],
+    /// Indices of the two corners on the side the T piece points to.
+    pub front: [usize; 2],
+}
+
+impl TSpinCorners {
+    pub fn new(fp: &FallingPiece<Piece>, playfield: &Playfield<Piece>) -> Self {
+        let center = (fp.x + 1, fp.y + 1);
+        let mut occupied = [false; 4];
+        for (i, (dx, dy)) in [(-1, -1), (1, -1), (-1, 1), (1, 1)].iter().enumerate() {
+            let x = center.0 + dx;
+            let y = center.1 + dy;
+            occupied[i] = (x < 0 || y < 0)
+                || playfield
+                    .grid
+                    .try_cell(x as usize, y as usize)
+                    .map_or(true, |c| !c.is_empty());
         }
-        if !fp.can_put_onto(playfield) {
-            fp.y += 1;
+        let front = match fp.rotation {
+            Rotation::Cw0 => [2, 3],
+            Rotation::Cw90 => [1, 3],
+            Rotation::Cw180 => [0, 1],
+            Rotation::Cw270 => [0, 2],
+        };
+        Self { occupied, front }
+    }
+    pub fn num_occupied(&self) -> usize {
+        self.occupied.iter().filter(|o| **o).count()
+    }
+    pub fn num_front_occupied(&self) -> usize {
+        self.front.iter().filter(|i| self.occupied[**i]).count()
+    }
+}
+
+fn check_tspin(fp: &FallingPiece<Piece>, playfield: &Playfield<Piece>) -> TSpin {
+    let n = TSpinCorners::new(fp, playfield).num_occupied();
+    let center = (fp.x + 1, fp.y + 1);
+    if n >= 3 {
+        // Check cell behinde the T piece.
+        let d = match fp.rotation {
+            Rotation::Cw0 => (0, -1),
+            Rotation::Cw90 => (-1, 0),
+            Rotation::Cw180 => (0, 1),
+            Rotation::Cw270 => (1, 0),
+        };
+        let x = center.0 + d.0;
+        let y = center.1 + d.1;
+        // outside or block
+        if (x < 0 || y < 0)
+            || playfield
+                .grid
+                .try_cell(x as usize, y as usize)
+                .map_or(true, |c| !c.is_empty())
+        {
+            if n == 4 {
+                TSpin::Normal // T-Spin triple variants
+            } else {
+                TSpin::Mini
+            }
+        } else {
+            TSpin::Normal
         }
-        fp
+    } else {
+        TSpin::None
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct WorldRuleLogic {
+    pub spin_detection: SpinDetection,
+    pub tspin_mini_rule: TSpinMiniRule,
+    /// Spawn rotation of each piece, indexed by `Piece as usize`.
+    pub spawn_rotations: [Rotation; 7],
+    /// Spawn x offset of each piece, indexed by `Piece as usize`.
+    pub spawn_x_offsets: [i32; 7],
+    /// Offset data of the I piece replacing the SRS one if set.
+    /// A different bounding box of the I piece can be emulated by shifting the offsets of
+    /// each rotation. All rotations must have the same number of kick tests.
+    pub i_offset_data: Option<OffsetData>,
+}
+
+impl WorldRuleLogic {
+    /// Same as `rotate` but also return the corners used for the T-Spin detection
+    /// if the piece is T, e.g. for highlighting them.
+    pub fn rotate_with_tspin_corners(
+        &self,
+        cw: bool,
+        falling_piece: &FallingPiece<Piece>,
+        playfield: &Playfield<Piece>,
+    ) -> Option<(RotationResult<Piece>, Option<TSpinCorners>)> {
+        let r = self.rotate(cw, falling_piece, playfield)?;
+        let corners = if r.falling_piece.piece == Piece::T {
+            Some(TSpinCorners::new(&r.falling_piece, playfield))
+        } else {
+            None
+        };
+        Some((r, corners))
     }
+
+    /// Same as `rotate` but accept only kicks satisfying `is_movable`,
+    /// e.g. for wrappers overriding `GameLogic::is_movable_to()`.
     /// References:
     /// * https://harddrop.com/wiki/SRS#How_Guideline_SRS_Really_Works
     /// * https://harddrop.com/wiki/T-Spin
-    fn rotate(
+    pub fn rotate_if(
         &self,
         cw: bool,
         falling_piece: &FallingPiece<Piece>,
         playfield: &Playfield<Piece>,
-    ) -> Option<(FallingPiece<Piece>, TSpin)> {
-        let mut fp = falling_piece.clone();
+        is_movable: impl Fn(&FallingPiece<Piece>) -> bool,
+    ) -> Option<RotationResult<Piece>> {
+        for (i, fp) in self.kick_candidates(cw, falling_piece).enumerate() {
+            if is_movable(&fp) {
+                let tspin = if fp.piece == Piece::T {
+                    match check_tspin(&fp, playfield) {
+                        TSpin::Mini
+                            if self.tspin_mini_rule == TSpinMiniRule::Kick4IsFull && i == 4 =>
+                        {
+                            TSpin::Normal
+                        }
+                        tspin => tspin,
+                    }
+                } else if self.spin_detection == SpinDetection::AllSpinImmobile
+                    && is_immobile(&fp, playfield)
+                {
+                    TSpin::Mini
+                } else {
+                    TSpin::None
+                };
+                return Some(RotationResult {
+                    falling_piece: fp,
+                    tspin,
+                    kick_index: i,
+                });
+            }
+        }
+        None
+    }
+
+    /// Iterate over rotated pieces with SRS kicks applied in test order.
+    fn kick_candidates(
+        &self,
+        cw: bool,
+        falling_piece: &FallingPiece<Piece>,
+    ) -> impl Iterator<Item = FallingPiece<Piece>> + '_ {
+        let mut fp = *falling_piece;
         fp.rotation = if cw {
             fp.rotation.cw()
         } else {
             fp.rotation.ccw()
         };
-        let offset_data = &match fp.piece {
-            Piece::I => &*OFFSET_DATA_I,
+        let offset_data: &OffsetData = match fp.piece {
+            Piece::I => self.i_offset_data.as_ref().unwrap_or(&*OFFSET_DATA_I),
             Piece::O => &*OFFSET_DATA_O,
             _ => &*OFFSET_DATA_JLSTZ,
         };
         let offsets1 = &offset_data[falling_piece.rotation as usize];
         let offsets2 = &offset_data[fp.rotation as usize];
-        for i in 0..offsets1.len() {
-            let mut fp = fp.clone();
-            fp.x += offsets1[i].0 - offsets2[i].0;
-            fp.y += offsets1[i].1 - offsets2[i].1;
-            if fp.can_put_onto(playfield) {
-                let tspin = if fp.piece == Piece::T {
-                    // check corder
-                    let mut n = 0;
-                    let center = (fp.x + 1, fp.y + 1);
-                    for dy in &[-1, 1] {
-                        for dx in &[-1, 1] {
-                            let x = center.0 + dx;
-                            let y = center.1 + dy;
-                            // outside or block
-                            if (x < 0 || y < 0)
-                                || !playfield.grid.is_valid_cell_index(x as usize, y as usize)
-                                || !playfield.grid.cell(x as usize, y as usize).is_empty()
-                            {
-                                n += 1;
-                            }
-                        }
-                    }
-                    if n >= 3 {
-                        // Check cell behinde the T piece.
-                        let d = match fp.rotation {
-                            Rotation::Cw0 => (0, -1),
-                            Rotation::Cw90 => (-1, 0),
-                            Rotation::Cw180 => (0, 1),
-                            Rotation::Cw270 => (1, 0),
-                        };
-                        let x = center.0 + d.0;
-                        let y = center.1 + d.1;
-                        // outside or block
-                        if (x < 0 || y < 0)
-                            || !playfield.grid.is_valid_cell_index(x as usize, y as usize)
-                            || !playfield.grid.cell(x as usize, y as usize).is_empty()
-                        {
-                            if n == 4 {
-                                TSpin::Normal // T-Spin triple variants
-                            } else {
-                                TSpin::Mini
-                            }
-                        } else {
-                            TSpin::Normal
-                        }
-                    } else {
-                        TSpin::None
-                    }
-                } else {
-                    TSpin::None
-                };
-                return Some((fp, tspin));
+        offsets1
+            .iter()
+            .zip(offsets2)
+            .map(move |(o1, o2)| FallingPiece {
+                x: fp.x + o1.0 - o2.0,
+                y: fp.y + o1.1 - o2.1,
+                ..fp
+            })
+    }
+}
+
+impl GameLogic<Piece> for WorldRuleLogic {
+    fn spawn_rotation(&self, piece: Piece) -> Rotation {
+        self.spawn_rotations[piece as usize]
+    }
+    fn spawn_x_offset(&self, piece: Piece) -> i32 {
+        self.spawn_x_offsets[piece as usize]
+    }
+    fn spawn_piece(&self, piece: Piece, playfield: &Playfield<Piece>) -> SpawnResult<Piece> {
+        let rotation = self.spawn_rotation(piece);
+        let g = piece.grid(rotation);
+        let top_pad = piece.grid_top_padding(rotation);
+        let mut fp = FallingPiece {
+            piece: piece,
+            x: ((playfield.grid.num_cols() - g.num_cols()) as i32) / 2 + self.spawn_x_offset(piece),
+            y: (playfield.visible_rows as i32) - (g.num_rows() - top_pad) as i32,
+            rotation,
+        };
+        if piece != Piece::I {
+            fp.y += 1;
+        }
+        if fp.can_put_onto(playfield) {
+            return SpawnResult::Placed(fp);
+        }
+        // Guideline: move up by one row if blocked.
+        fp.y += 1;
+        if fp.can_put_onto(playfield) {
+            return SpawnResult::NudgedUp(fp);
+        }
+        SpawnResult::Blocked
+    }
+    fn rotate(
+        &self,
+        cw: bool,
+        falling_piece: &FallingPiece<Piece>,
+        playfield: &Playfield<Piece>,
+    ) -> Option<RotationResult<Piece>> {
+        self.rotate_if(cw, falling_piece, playfield, |fp| {
+            self.is_movable_to(fp, playfield)
+        })
+    }
+    fn rotation_candidates(
+        &self,
+        cw: bool,
+        falling_piece: &FallingPiece<Piece>,
+        playfield: &Playfield<Piece>,
+    ) -> Vec<(FallingPiece<Piece>, bool)> {
+        self.kick_candidates(cw, falling_piece)
+            .map(|fp| (fp, self.is_movable_to(&fp, playfield)))
+            .collect()
+    }
+}
+
+//--- Presets
+
+impl GameConfig<WorldRuleLogic> {
+    /// Endless mode with `GameParams::guideline()`.
+    pub fn marathon() -> Self {
+        Self {
+            logic: WorldRuleLogic::default(),
+            params: GameParams::guideline(),
+        }
+    }
+    /// Time attack mode of 40 lines without ARE and line clear delay.
+    pub fn sprint() -> Self {
+        Self {
+            logic: WorldRuleLogic::default(),
+            params: GameParams {
+                are: 0,
+                line_clear_delay: 0.into(),
+                line_goal: Some(40),
+                ..GameParams::guideline()
+            },
+        }
+    }
+    /// Score attack mode of 2 minutes with `GameParams::guideline()`.
+    pub fn ultra() -> Self {
+        Self {
+            logic: WorldRuleLogic::default(),
+            params: GameParams {
+                time_limit: Some(2 * 60 * 60),
+                ..GameParams::guideline()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::tests::playfield_from_rows;
+    use crate::common::{Cell, CompactGrid, GameBuilder, GameStateId, TransitionValidator};
+
+    #[test]
+    fn occupied_offsets() {
+        assert_eq!(
+            &[(0, 1), (1, 1), (2, 1), (1, 2)],
+            Piece::T.occupied_offsets(Rotation::Cw0)
+        );
+        for p in Piece::slice() {
+            for &r in &[
+                Rotation::Cw0,
+                Rotation::Cw90,
+                Rotation::Cw180,
+                Rotation::Cw270,
+            ] {
+                let offsets = p.occupied_offsets(r);
+                assert_eq!(4, offsets.len());
+                let grid = p.grid(r);
+                assert!(offsets
+                    .iter()
+                    .all(|&(x, y)| matches!(grid.cell(x, y), Cell::Block(_))));
             }
         }
-        None
+    }
+
+    #[test]
+    fn all_spin_immobile() {
+        let playfield = playfield_from_rows(&[
+            "X.........",
+            "..........",
+            "X.XXXXXXXX",
+            "X..XXXXXXX",
+            "XX.XXXXXXX",
+        ]);
+        let fp = FallingPiece {
+            piece: Piece::S,
+            x: 0,
+            y: 2,
+            rotation: Rotation::Cw0,
+        };
+        assert!(fp.can_put_onto(&playfield));
+
+        let logic = WorldRuleLogic::default();
+        let r = logic.rotate(true, &fp, &playfield).unwrap();
+        assert_eq!((0, 0), (r.falling_piece.x, r.falling_piece.y));
+        assert_eq!(TSpin::None, r.tspin);
+
+        let logic = WorldRuleLogic {
+            spin_detection: SpinDetection::AllSpinImmobile,
+            ..WorldRuleLogic::default()
+        };
+        let r = logic.rotate(true, &fp, &playfield).unwrap();
+        assert_eq!((0, 0), (r.falling_piece.x, r.falling_piece.y));
+        assert_eq!(TSpin::Mini, r.tspin);
+
+        // not immobile
+        let fp = FallingPiece {
+            piece: Piece::S,
+            x: 4,
+            y: 3,
+            rotation: Rotation::Cw0,
+        };
+        let r = logic.rotate(true, &fp, &playfield).unwrap();
+        assert_eq!(TSpin::None, r.tspin);
+    }
+
+    #[test]
+    fn tspin_corners() {
+        // T-Spin Double
+        let playfield = playfield_from_rows(&["..X.......", "XX...XXXXX", "XXX.XXXXXX"]);
+        let fp = FallingPiece {
+            piece: Piece::T,
+            x: 2,
+            y: 0,
+            rotation: Rotation::Cw270,
+        };
+        let logic = WorldRuleLogic::default();
+        let (r, corners) = logic
+            .rotate_with_tspin_corners(false, &fp, &playfield)
+            .unwrap();
+        assert_eq!((2, 0), (r.falling_piece.x, r.falling_piece.y));
+        assert_eq!(TSpin::Normal, r.tspin);
+        let corners = corners.unwrap();
+        assert_eq!([true, true, true, false], corners.occupied);
+        assert_eq!([0, 1], corners.front);
+        assert_eq!(3, corners.num_occupied());
+        assert_eq!(2, corners.num_front_occupied());
+
+        let fp = FallingPiece {
+            piece: Piece::I,
+            x: 3,
+            y: 10,
+            rotation: Rotation::Cw0,
+        };
+        let (_, corners) = logic
+            .rotate_with_tspin_corners(true, &fp, &playfield)
+            .unwrap();
+        assert!(corners.is_none());
+    }
+
+    #[test]
+    fn tspin_triple_with_last_kick() {
+        let mut playfield = playfield_from_rows(&[
+            "....XXXXXX",
+            "XX...XXXXX",
+            "XXXX.XXXXX",
+            "XXX..XXXXX",
+            "XXXX.XXXXX",
+        ]);
+        let fp = FallingPiece {
+            piece: Piece::T,
+            x: 2,
+            y: 2,
+            rotation: Rotation::Cw0,
+        };
+        for &tspin_mini_rule in &[TSpinMiniRule::Classic, TSpinMiniRule::Kick4IsFull] {
+            let logic = WorldRuleLogic {
+                tspin_mini_rule,
+                ..WorldRuleLogic::default()
+            };
+            let r = logic.rotate(false, &fp, &playfield).unwrap();
+            assert_eq!(4, r.kick_index);
+            assert_eq!((3, 0), (r.falling_piece.x, r.falling_piece.y));
+            assert_eq!(TSpin::Normal, r.tspin);
+        }
+        let r = WorldRuleLogic::default()
+            .rotate(false, &fp, &playfield)
+            .unwrap();
+        assert_eq!(Ok(3), playfield.lock_piece(&r.falling_piece));
+    }
+
+    #[test]
+    fn kick_index() {
+        let playfield = playfield_from_rows(&[
+            ".X........",
+            "..........",
+            "X.XXXXXXXX",
+            "..XXXXXXXX",
+            "X.XXXXXXXX",
+        ]);
+        let logic = WorldRuleLogic::default();
+
+        // T-Spin Triple
+        let fp = FallingPiece {
+            piece: Piece::T,
+            x: 0,
+            y: 2,
+            rotation: Rotation::Cw180,
+        };
+        let r = logic.rotate(true, &fp, &playfield).unwrap();
+        assert_eq!((0, 0), (r.falling_piece.x, r.falling_piece.y));
+        assert_eq!(TSpin::Normal, r.tspin);
+        assert_eq!(3, r.kick_index);
+
+        let candidates = logic.rotation_candidates(true, &fp, &playfield);
+        assert_eq!(5, candidates.len());
+        let i = candidates.iter().position(|c| c.1).unwrap();
+        assert_eq!(r.kick_index, i);
+        let c = candidates[i].0;
+        assert_eq!((r.falling_piece.x, r.falling_piece.y), (c.x, c.y));
+        assert!(candidates
+            .iter()
+            .all(|c| c.0.rotation as usize == Rotation::Cw270 as usize));
+
+        // no kick
+        let fp = FallingPiece {
+            piece: Piece::T,
+            x: 4,
+            y: 5,
+            rotation: Rotation::Cw0,
+        };
+        let r = logic.rotate(true, &fp, &playfield).unwrap();
+        assert_eq!(TSpin::None, r.tspin);
+        assert_eq!(0, r.kick_index);
+    }
+
+    #[test]
+    fn presets() {
+        let marathon = GameConfig::marathon().params;
+        assert_eq!(1.0 / 60.0, marathon.gravity);
+        assert_eq!(20.0 / 60.0, marathon.soft_drop_gravity);
+        assert_eq!(30, marathon.lock_delay);
+        assert_eq!((10, 2), (marathon.das, marathon.arr));
+        assert_eq!(6, marathon.are);
+        assert_eq!(30, marathon.line_clear_delay.get(4, TSpin::None));
+
+        let sprint = GameConfig::sprint().params;
+        assert_eq!(marathon.gravity, sprint.gravity);
+        assert_eq!(marathon.lock_delay, sprint.lock_delay);
+        assert_eq!(0, sprint.are);
+        assert_eq!(0, sprint.line_clear_delay.get(1, TSpin::None));
+        assert_eq!((None, Some(40)), (marathon.line_goal, sprint.line_goal));
+
+        let ultra = GameConfig::ultra().params;
+        assert_eq!(marathon.are, ultra.are);
+        assert_eq!((None, Some(7200)), (ultra.line_goal, ultra.time_limit));
+    }
+
+    #[test]
+    fn ultra_with_transition_validator() {
+        let mut game = GameBuilder::new(GameConfig::ultra(), playfield_from_rows(&[]))
+            .next_pieces(Piece::slice().repeat(4).into())
+            .build()
+            .unwrap();
+        game.set_transition_validator(Some(TransitionValidator::standard()));
+        assert!(game.update_until(GameStateId::Finished, 7200));
+        assert_eq!(7200, game.data().elapsed_frames);
+    }
+
+    #[test]
+    fn spawn_piece_nudge() {
+        let logic = WorldRuleLogic::default();
+        let playfield = playfield_from_rows(&[]);
+        let fp = match logic.spawn_piece(Piece::T, &playfield) {
+            SpawnResult::Placed(fp) => fp,
+            r => panic!("{:?}", r),
+        };
+        assert_eq!((3, 18), (fp.x, fp.y));
+
+        // T occupies y=19-20 at first
+        let mut playfield = playfield_from_rows(&[]);
+        playfield.grid.set_cell(4, 19, Cell::Garbage);
+        let fp = match logic.spawn_piece(Piece::T, &playfield) {
+            SpawnResult::NudgedUp(fp) => fp,
+            r => panic!("{:?}", r),
+        };
+        assert_eq!((3, 19), (fp.x, fp.y));
+
+        // Near-full board up to the top visible row.
+        let mut playfield = playfield_from_rows(&vec!["XXXXXXXXX."; 20]);
+        let fp = match logic.spawn_piece(Piece::T, &playfield) {
+            SpawnResult::NudgedUp(fp) => fp,
+            r => panic!("{:?}", r),
+        };
+        assert_eq!((3, 19), (fp.x, fp.y));
+        assert!(fp.can_put_onto(&playfield));
+
+        playfield.grid.set_cell(4, 20, Cell::Garbage);
+        assert!(matches!(
+            logic.spawn_piece(Piece::T, &playfield),
+            SpawnResult::Blocked
+        ));
+    }
+
+    #[test]
+    fn tspin_mini_rule() {
+        let playfield = playfield_from_rows(&[
+            "..X.X.....",
+            "..........",
+            "...XX.....",
+            ".X........",
+            "....X.....",
+        ]);
+        let fp = FallingPiece {
+            piece: Piece::T,
+            x: 1,
+            y: 0,
+            rotation: Rotation::Cw90,
+        };
+
+        let logic = WorldRuleLogic::default();
+        let r = logic.rotate(false, &fp, &playfield).unwrap();
+        assert_eq!((2, 2), (r.falling_piece.x, r.falling_piece.y));
+        assert_eq!(4, r.kick_index);
+        assert_eq!(TSpin::Mini, r.tspin);
+
+        let logic = WorldRuleLogic {
+            tspin_mini_rule: TSpinMiniRule::Kick4IsFull,
+            ..WorldRuleLogic::default()
+        };
+        let r = logic.rotate(false, &fp, &playfield).unwrap();
+        assert_eq!(4, r.kick_index);
+        assert_eq!(TSpin::Normal, r.tspin);
+    }
+
+    #[test]
+    fn compact_encoding() {
+        let mut grid = playfield_from_rows(&["X.XXXXXXXX"]).grid;
+        for (x, p) in Piece::slice().iter().enumerate() {
+            grid.set_cell(x, 1, Cell::Block(*p));
+        }
+        grid.set_cell(9, 2, Cell::Ghost(Piece::L));
+        let bytes = grid.to_compact();
+        assert_eq!(10 * 40 * 4 / 8, bytes.len());
+        let decoded = PieceGrid::from_compact(10, 40, &bytes).unwrap();
+        assert_eq!(format!("{:?}", grid), format!("{:?}", decoded));
+        assert!(PieceGrid::from_compact(10, 39, &bytes).is_err());
+    }
+
+    #[test]
+    fn spawn_rotation() {
+        let mut logic = WorldRuleLogic::default();
+        logic.spawn_rotations[Piece::T as usize] = Rotation::Cw180;
+        let playfield = playfield_from_rows(&[]);
+        let fp = logic
+            .spawn_piece(Piece::T, &playfield)
+            .falling_piece()
+            .unwrap();
+        assert_eq!(Rotation::Cw180 as usize, fp.rotation as usize);
+        assert!(fp.can_put_onto(&playfield));
+        let fp = logic
+            .spawn_piece(Piece::S, &playfield)
+            .falling_piece()
+            .unwrap();
+        assert_eq!(Rotation::Cw0 as usize, fp.rotation as usize);
+    }
+
+    #[test]
+    fn spawn_x_offset() {
+        let mut logic = WorldRuleLogic::default();
+        let playfield = playfield_from_rows(&[]);
+        let x = logic
+            .spawn_piece(Piece::T, &playfield)
+            .falling_piece()
+            .unwrap()
+            .x;
+        logic.spawn_x_offsets[Piece::T as usize] = 1;
+        let fp = logic
+            .spawn_piece(Piece::T, &playfield)
+            .falling_piece()
+            .unwrap();
+        assert_eq!(x + 1, fp.x);
+        let fp = logic
+            .spawn_piece(Piece::S, &playfield)
+            .falling_piece()
+            .unwrap();
+        assert_eq!(x, fp.x);
+    }
+
+    #[test]
+    fn try_rotate() {
+        let logic = WorldRuleLogic::default();
+        // The I piece can not be horizontal in 3 columns.
+        let playfield = Playfield {
+            visible_rows: 20,
+            grid: PieceGrid::new(3, 40, vec![]),
+        };
+        let fp = FallingPiece {
+            piece: Piece::I,
+            x: -1,
+            y: 10,
+            rotation: Rotation::Cw90,
+        };
+        assert!(logic.rotate(true, &fp, &playfield).is_none());
+        let r = logic.try_rotate(true, &fp, &playfield).unwrap_err();
+        assert_eq!(grid::OverlayResult::OVERFLOW, r.overlay);
+
+        let mut playfield = playfield_from_rows(&[]);
+        playfield.grid.fill_rows(0..10, Cell::Garbage);
+        let fp = FallingPiece {
+            piece: Piece::T,
+            x: 3,
+            y: 4,
+            rotation: Rotation::Cw0,
+        };
+        for (x, y) in [(4, 6), (3, 5), (4, 5), (5, 5)].iter() {
+            playfield.grid.set_cell(*x, *y, Cell::Empty);
+        }
+        assert!(fp.can_put_onto(&playfield));
+        let r = logic.try_rotate(true, &fp, &playfield).unwrap_err();
+        assert_eq!(grid::OverlayResult::OVERLAP, r.overlay);
+
+        let playfield = playfield_from_rows(&[]);
+        let fp = FallingPiece { y: 10, ..fp };
+        let r = logic.try_rotate(true, &fp, &playfield).unwrap();
+        assert_eq!(0, r.kick_index);
+    }
+
+    #[test]
+    fn i_offset_data() {
+        let playfield =
+            playfield_from_rows(&["XXXX.XXXXX", "XXXX.XXXXX", "XXXX.XXXXX", ".....XXXXX"]);
+        let fp = FallingPiece {
+            piece: Piece::I,
+            x: -1,
+            y: -2,
+            rotation: Rotation::Cw0,
+        };
+        assert!(fp.can_put_onto(&playfield));
+        let mut logic = WorldRuleLogic::default();
+        assert!(logic.rotate(true, &fp, &playfield).is_none());
+        logic.i_offset_data = Some([
+            vec![(0, 0), (0, 0)],
+            vec![(-1, 0), (-3, -2)],
+            vec![(-1, 1), (-1, 1)],
+            vec![(0, 1), (0, 1)],
+        ]);
+        let r = logic.rotate(true, &fp, &playfield).unwrap();
+        assert_eq!(1, r.kick_index);
+        assert_eq!((2, 0), (r.falling_piece.x, r.falling_piece.y));
+        // Other pieces are not affected.
+        let fp = FallingPiece {
+            piece: Piece::T,
+            x: 4,
+            y: 10,
+            rotation: Rotation::Cw0,
+        };
+        let r = logic.rotate(true, &fp, &playfield).unwrap();
+        assert_eq!(0, r.kick_index);
     }
 }
@@ -0,0 +1,104 @@
+//! Zobrist hashing of boards for AI transposition tables.
+//!
+//! Reference: https://en.wikipedia.org/wiki/Zobrist_hashing
+
+use super::common::{Cell, GameRng};
+use super::tetro::{Piece, PieceGrid};
+use alloc::vec::Vec;
+
+/// Garbage and blocks of each piece.
+const NUM_CELL_KINDS: usize = 8;
+
+/// Index of the key for `cell`, or None for empty cells (including ghosts).
+fn cell_kind(cell: &Cell<Piece>) -> Option<usize> {
+    match cell {
+        Cell::Empty | Cell::Ghost(_) => None,
+        Cell::Block(p) => Some(*p as usize),
+        Cell::Garbage => Some(Piece::num()),
+    }
+}
+
+/// Precomputed random keys per `(x, y, cell kind)`.
+/// The hash of an empty board is 0.
+#[derive(Debug, Clone)]
+pub struct ZobristHasher {
+    num_cols: usize,
+    num_rows: usize,
+    keys: Vec<u64>,
+}
+
+impl ZobristHasher {
+    pub fn new(num_cols: usize, num_rows: usize, seed: u64) -> Self {
+        let mut rng = GameRng::new(seed);
+        let keys = (0..num_cols * num_rows * NUM_CELL_KINDS)
+            .map(|_| rng.next_u64())
+            .collect();
+        Self {
+            num_cols,
+            num_rows,
+            keys,
+        }
+    }
+
+    fn key(&self, x: usize, y: usize, cell: &Cell<Piece>) -> u64 {
+        assert!(x < self.num_cols);
+        assert!(y < self.num_rows);
+        match cell_kind(cell) {
+            Some(kind) => self.keys[(x + y * self.num_cols) * NUM_CELL_KINDS + kind],
+            None => 0,
+        }
+    }
+
+    pub fn hash(&self, grid: &PieceGrid) -> u64 {
+        let mut key = 0;
+        for y in 0..grid.num_rows() {
+            for x in 0..grid.num_cols() {
+                self.toggle(&mut key, x, y, &grid.cell(x, y));
+            }
+        }
+        key
+    }
+
+    /// Add or remove `cell` at (x, y) to/from `key`.
+    /// To update `key` for a cell change, toggle both the old and the new cells.
+    pub fn toggle(&self, key: &mut u64, x: usize, y: usize, cell: &Cell<Piece>) {
+        *key ^= self.key(x, y, cell);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn incremental_update() {
+        let hasher = ZobristHasher::new(10, 40, 1);
+        let mut grid = PieceGrid::new(10, 40, vec![]);
+        let mut key = hasher.hash(&grid);
+        assert_eq!(0, key);
+        let changes = [
+            (0, 0, Cell::Garbage),
+            (3, 5, Cell::Block(Piece::T)),
+            (3, 5, Cell::Block(Piece::S)),
+            (9, 39, Cell::Block(Piece::I)),
+            (0, 0, Cell::Empty),
+            (4, 4, Cell::Ghost(Piece::O)),
+        ];
+        for (x, y, cell) in changes.iter() {
+            hasher.toggle(&mut key, *x, *y, &grid.cell(*x, *y));
+            hasher.toggle(&mut key, *x, *y, cell);
+            grid.set_cell(*x, *y, *cell);
+            assert_eq!(hasher.hash(&grid), key);
+        }
+        assert_ne!(0, key);
+
+        let mut other = PieceGrid::new(10, 40, vec![]);
+        other.set_cell(3, 5, Cell::Block(Piece::T));
+        other.set_cell(9, 39, Cell::Block(Piece::I));
+        assert_ne!(hasher.hash(&grid), hasher.hash(&other));
+        assert_eq!(
+            hasher.hash(&grid),
+            ZobristHasher::new(10, 40, 1).hash(&grid)
+        );
+    }
+}
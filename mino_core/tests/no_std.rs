@@ -0,0 +1,36 @@
+//! Check that the core logic is usable from a `no_std` crate.
+//! For a full check, build `mino_core` for a bare metal target (see the CI workflow).
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec;
+use mino_core::common::{Cell, FallingPiece, GameLogic, Playfield, Rotation, TSpin};
+use mino_core::tetro::{Piece, PieceGrid, WorldRuleLogic};
+
+#[test]
+fn collision_and_rotation() {
+    let mut grid = PieceGrid::new(10, 40, vec![]);
+    grid.fill_row(0, Cell::Garbage);
+    let playfield = Playfield {
+        visible_rows: 20,
+        grid,
+    };
+    let logic = WorldRuleLogic::default();
+    let fp = logic
+        .spawn_piece(Piece::T, &playfield)
+        .falling_piece()
+        .unwrap();
+    assert!(fp.can_put_onto(&playfield));
+    assert_eq!(fp.y as usize, fp.droppable_rows(&playfield));
+
+    let dropped = FallingPiece {
+        y: fp.y - fp.droppable_rows(&playfield) as i32,
+        ..fp
+    };
+    assert!(dropped.try_move(0, -1, &playfield).is_none());
+    let r = logic.rotate(true, &dropped, &playfield).unwrap();
+    assert_eq!(Rotation::Cw90 as usize, r.falling_piece.rotation as usize);
+    assert_eq!(TSpin::None, r.tspin);
+}
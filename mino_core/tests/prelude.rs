@@ -0,0 +1,24 @@
+//! Check that a game can be built only with the prelude.
+
+use mino_core::prelude::*;
+
+#[test]
+fn build_game() {
+    let config = GameConfig {
+        params: GameParams::default(),
+        logic: WorldRuleLogic::default(),
+    };
+    let playfield = Playfield {
+        visible_rows: 20,
+        grid: PieceGrid::new(10, 40, vec![]),
+    };
+    let mut game = GameBuilder::new(config, playfield)
+        .next_pieces(Piece::slice().iter().copied().collect())
+        .build()
+        .unwrap();
+    assert_eq!(GameStateId::Play, game.state_id());
+    let fp: FallingPiece<Piece> = game.data().falling_piece.unwrap();
+    assert!(matches!(fp.piece, Piece::I));
+    game.update(Input::HARD_DROP);
+    assert_eq!(GameStateId::Lock, game.state_id());
+}
@@ -27,6 +27,19 @@ impl EventHandlerManager {
     fn get(&self, id: EventHandlerId) -> Option<&Box<dyn EventHandler>> {
         self.handlers.get(&id)
     }
+    fn len(&self) -> usize {
+        self.handlers.len()
+    }
+    fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+    /// Remove all handlers. If `reset_id` is true, ids are reused from the beginning.
+    fn clear(&mut self, reset_id: bool) {
+        self.handlers.clear();
+        if reset_id {
+            self.last_id = 0;
+        }
+    }
 }
 
 impl EventHandler for EventHandlerManager {
@@ -88,4 +101,27 @@ pub fn run() {
     {
         println!("Bob hello_count: {}", handler.hello_count);
     }
+    println!("handlers: {}", mgr.len());
+    mgr.clear(false);
+    println!("empty: {}", mgr.is_empty());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clear() {
+        let mut mgr = EventHandlerManager::default();
+        mgr.add(Box::new(SomeoneHandler::new("Alice")));
+        mgr.add(Box::new(SomeoneHandler::new("Bob")));
+        mgr.add(Box::new(SomeoneHandler::new("Carol")));
+        assert_eq!(3, mgr.len());
+        mgr.clear(false);
+        assert!(mgr.is_empty());
+        assert_eq!(4, mgr.add(Box::new(SomeoneHandler::new("Dave"))));
+        mgr.clear(true);
+        assert_eq!(0, mgr.len());
+        assert_eq!(1, mgr.add(Box::new(SomeoneHandler::new("Eve"))));
+    }
 }